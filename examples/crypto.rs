@@ -1,9 +1,14 @@
 use async_std::task;
 use data_watch::actors::consumer::StdoutConsumer;
-use data_watch::actors::messages::{Stop, WebProducerSchedule};
-use data_watch::actors::producer::{ApiRequestType, ProducerAction};
+use data_watch::actors::messages::{AddRateLimit, Stop, WebProducerSchedule};
+use data_watch::actors::producer::{
+    ApiRequestType, ConditionalRequest, ProducerAction, Rate, RateLimitConfig, RequestLimits,
+    ResponseFormat,
+};
 use data_watch::actors::Scheduler;
-use data_watch::SharedVar;
+use data_watch::jsonutility::Flatten;
+use data_watch::schedule::ScheduleType;
+use data_watch::{Labels, SharedVar};
 use std::collections::HashMap;
 use std::env;
 use std::sync::{Arc, RwLock};
@@ -48,18 +53,41 @@ async fn main() -> Result<(), xactor::Error> {
     // start csvwriter to push output to csv file
     // let _csvwriter = CsvWriter::default().start().await?;
 
+    // coinbase requests share one limit of 3 per second, found by the host of their url
+    scheduler_addr.send(AddRateLimit(RateLimitConfig {
+        name: String::from("COINBASE"),
+        hosts: vec![String::from("api.pro.coinbase.com")],
+        rates: vec![Rate {
+            requests: 3,
+            per: Duration::from_secs(1),
+        }],
+    }))?;
+
     // Build Request to Retreive Crypto Currency prices from coinbase
     let coinbase1 = WebProducerSchedule {
         source_name: String::from("COINBASE_PRO"),
         api_url: String::from("https://api.pro.coinbase.com/products/BTC-USD/ticker"),
         request_type: ApiRequestType::GET,
         body: None,
-        header: None,
+        headers: Vec::new(),
+        auth: None,
+        rate_limit: None,
         //                   sec min hour dayofmonth month  dayofweek
-        cron: String::from("0  */1  *   *  *  *"),
+        schedule: ScheduleType::Cron(String::from("0  */1  *   *  *  *")),
+        calendar: None,
+        timezone: None,
         jmespatch_query: String::from(
             "merge({ measure_data: {mark: to_number(price)} }, { measure_name: `\"BTC-USD\"`})",
         ),
+        format: ResponseFormat::Json,
+        lenient: false,
+        flatten: Flatten::default(),
+        labels: Labels::new(),
+        retry: None,
+        limits: RequestLimits::default(),
+        conditional: ConditionalRequest::default(),
+        pagination: None,
+        depends_on: Vec::new(),
         storage_var: shared_variables.clone(),
         response_action: ProducerAction::PUBLISHDATA,
     };
@@ -73,12 +101,25 @@ async fn main() -> Result<(), xactor::Error> {
         api_url: String::from("https://api.pro.coinbase.com/products/ETH-USD/ticker"),
         request_type: ApiRequestType::GET,
         body: None,
-        header: None,
+        headers: Vec::new(),
+        auth: None,
+        rate_limit: None,
         //                   sec min hour dayofmonth month  dayofweek
-        cron: String::from("30  */1  *   *  *  *"),
+        schedule: ScheduleType::Cron(String::from("30  */1  *   *  *  *")),
+        calendar: None,
+        timezone: None,
         jmespatch_query: String::from(
             "merge({ measure_data: {mark: to_number(price)} }, { measure_name: `\"ETH-USD\"`})",
         ),
+        format: ResponseFormat::Json,
+        lenient: false,
+        flatten: Flatten::default(),
+        labels: Labels::new(),
+        retry: None,
+        limits: RequestLimits::default(),
+        conditional: ConditionalRequest::default(),
+        pagination: None,
+        depends_on: Vec::new(),
         storage_var: shared_variables.clone(),
         response_action: ProducerAction::PUBLISHDATA,
     };
@@ -92,12 +133,25 @@ async fn main() -> Result<(), xactor::Error> {
         api_url: String::from("https://api.pro.coinbase.com/products/COMP-USD/ticker"),
         request_type: ApiRequestType::GET,
         body: None,
-        header: None,
+        headers: Vec::new(),
+        auth: None,
+        rate_limit: None,
         //                   sec min hour dayofmonth month  dayofweek
-        cron: String::from("30  */1  *   *  *  *"),
+        schedule: ScheduleType::Cron(String::from("30  */1  *   *  *  *")),
+        calendar: None,
+        timezone: None,
         jmespatch_query: String::from(
             "merge({ measure_data: {mark: to_number(price)} }, { measure_name: `\"COMP-USD\"`})",
         ),
+        format: ResponseFormat::Json,
+        lenient: false,
+        flatten: Flatten::default(),
+        labels: Labels::new(),
+        retry: None,
+        limits: RequestLimits::default(),
+        conditional: ConditionalRequest::default(),
+        pagination: None,
+        depends_on: Vec::new(),
         storage_var: shared_variables.clone(),
         response_action: ProducerAction::PUBLISHDATA,
     };
@@ -111,10 +165,23 @@ async fn main() -> Result<(), xactor::Error> {
             api_url: String::from("https://api.compound.finance/api/v2/account?addresses[]=[[ETHPUBADDRESS]]"), 
             request_type: ApiRequestType::GET,
             body: None,
-            header: None,
+            headers: Vec::new(),
+            auth: None,
+            rate_limit: None,
             //                   sec min hour dayofmonth month  dayofweek
-            cron: String::from("15  */1  *   *  *  *"),
+            schedule: ScheduleType::Cron(String::from("15  */1  *   *  *  *")),
+            calendar: None,
+            timezone: None,
             jmespatch_query: String::from("accounts[0].tokens[].{measure_name: symbol, measure_data: { balance: to_number(supply_balance_underlying.value), accrued: to_number(lifetime_supply_interest_accrued.value)} }"), 
+            format: ResponseFormat::Json,
+            lenient: false,
+            flatten: Flatten::default(),
+            labels: Labels::new(),
+            retry: None,
+            limits: RequestLimits::default(),
+            conditional: ConditionalRequest::default(),
+            pagination: None,
+            depends_on: Vec::new(),
             storage_var: shared_variables.clone(),
             response_action: ProducerAction::PUBLISHDATA,
         };
@@ -128,10 +195,23 @@ async fn main() -> Result<(), xactor::Error> {
             api_url: String::from("https://api.compound.finance/api/v2/ctoken"), 
             request_type: ApiRequestType::GET,
             body: None,
-            header: None,
+            headers: Vec::new(),
+            auth: None,
+            rate_limit: None,
             //                   sec min hour dayofmonth month  dayofweek
-            cron: String::from("45  */1  *   *  *  *"),
+            schedule: ScheduleType::Cron(String::from("45  */1  *   *  *  *")),
+            calendar: None,
+            timezone: None,
             jmespatch_query: String::from("cToken[?symbol==`\"cUSDC\"`||symbol==`\"cDAI\"`].{measure_name: symbol, measure_data: {supply_rate: to_number(supply_rate.value)}}"), 
+            format: ResponseFormat::Json,
+            lenient: false,
+            flatten: Flatten::default(),
+            labels: Labels::new(),
+            retry: None,
+            limits: RequestLimits::default(),
+            conditional: ConditionalRequest::default(),
+            pagination: None,
+            depends_on: Vec::new(),
             storage_var: shared_variables.clone(),
             response_action: ProducerAction::PUBLISHDATA,
         };
@@ -1,9 +1,15 @@
 use async_std::task;
 use data_watch::actors::consumer::StdoutConsumer;
-use data_watch::actors::messages::{Stop, WebProducerSchedule};
-use data_watch::actors::producer::{ApiRequestType, ProducerAction};
+use data_watch::actors::messages::{AddAuthProvider, AddCalendar, Stop, WebProducerSchedule};
+use data_watch::actors::producer::{
+    ApiRequestType, ConditionalRequest, OAuth2Config, OAuth2Grant, ProducerAction, RequestAuth,
+    RequestLimits, ResponseFormat,
+};
 use data_watch::actors::Scheduler;
-use data_watch::SharedVar;
+use data_watch::calendar::Calendar;
+use data_watch::jsonutility::Flatten;
+use data_watch::schedule::ScheduleType;
+use data_watch::{Labels, SharedVar};
 use std::collections::HashMap;
 use std::env;
 use std::sync::{Arc, RwLock};
@@ -13,10 +19,9 @@ use xactor::Actor;
 // Example that grabs current quotes from tdameritrade's api using current token
 //
 // Need to request token using a valid refresh token which is stored in shared_variables
-// Token can then be used to make quote requests.  The active token will be renewed every 1700 seconds
+// The OAuth2 provider requests the token and adds it to the quote requests, renewing it before it expires
 //
-// Example uses both a GET request for the quotes and a POST request for refreshing new Token
-// and shows examples of using shared_variables
+// Example shows an OAuth2 provider with the refresh token grant and examples of using shared_variables
 //
 // API documentation at https://developer.tdameritrade.com/
 
@@ -50,36 +55,29 @@ async fn main() -> Result<(), xactor::Error> {
         scheduler.wait_for_stop().await;
     });
 
+    // skip market holidays on every schedule that references the NYSE calendar
+    let holidays = Calendar::from_file("NYSE", "examples/nyse_holidays.txt")
+        .expect("NYSE holiday calendar is missing");
+    scheduler_addr.send(AddCalendar(holidays))?;
+
     // start datawriter to push output to screen
     let _datawriter = StdoutConsumer.start().await?;
 
     // start csvwriter to push output to csv file
     // let _csvwriter = CsvWriter::default().start().await?;
 
-    // example using POST request configuration and response_action into variable
-    // TODO: need to add header or body and request type: GET / POST
-
-    // Build Request to use refresh token to get a valid access token
-    let request_token_refresh = WebProducerSchedule {
-        source_name: String::from("TD_AUTH"),
-        api_url: String::from("https://api.tdameritrade.com/v1/oauth2/token"),
-        request_type: ApiRequestType::POST,
-        body: Some(String::from(
-            "grant_type=refresh_token&refresh_token=[[TDREFRESHTOKEN]]&client_id=[[TDCLIENTID]]",
-        )),
-        header: None,
-        //                   sec min hour dayofmonth month  dayofweek
-        cron: String::from("0  */30  9-16   *  *  1-5"),
-        jmespatch_query: String::from("{ TDTOKEN: access_token }"),
+    // OAuth2 provider uses the refresh token to get an access token, the token is cached
+    // and renewed a minute before it expires
+    scheduler_addr.send(AddAuthProvider(OAuth2Config {
+        name: String::from("TD"),
+        token_url: String::from("https://api.tdameritrade.com/v1/oauth2/token"),
+        grant: OAuth2Grant::RefreshToken(String::from("[[TDREFRESHTOKEN]]")),
+        client_id: String::from("[[TDCLIENTID]]"),
+        client_secret: None,
+        scope: None,
+        refresh_margin: Duration::from_secs(60),
         storage_var: shared_variables.clone(),
-        response_action: ProducerAction::STOREVARIABLE,
-    };
-
-    // Send Request to scheduler
-    scheduler_addr.send(request_token_refresh)?;
-
-    // need to wait for authorization above for request below to work
-    task::sleep(Duration::from_secs(1)).await;
+    }))?;
 
     // Build request to use valid token to grab current quotes on a 15 minute cycle
     let request_stock_quotes = WebProducerSchedule {
@@ -87,10 +85,23 @@ async fn main() -> Result<(), xactor::Error> {
         api_url: String::from("https://api.tdameritrade.com/v1/marketdata/quotes?symbol=[[TDQUOTESYMBOLS]]"), 
         request_type: ApiRequestType::GET,
         body: None,
-        header: Some((String::from("Authorization"), String::from("Bearer [[TDTOKEN]]"))),
+        headers: Vec::new(),
+        auth: Some(RequestAuth::OAuth2(String::from("TD"))),
+        rate_limit: None,
         //                   sec min hour dayofmonth month  dayofweek
-        cron: String::from("10  */15  9-16   *  *  1-5"),
+        schedule: ScheduleType::Cron(String::from("10  */15  9-16   *  *  Mon-Fri")),
+        calendar: Some(String::from("NYSE")),
+        timezone: Some(String::from("America/New_York")),
         jmespatch_query: String::from("*.{measure_name: symbol, measure_data: {lastPrice: lastPrice, highPrice: highPrice, lowPrice: lowPrice, mark: mark}}"), 
+        format: ResponseFormat::Json,
+        lenient: false,
+        flatten: Flatten::default(),
+        labels: Labels::new(),
+        retry: None,
+        limits: RequestLimits::default(),
+        conditional: ConditionalRequest::default(),
+        pagination: None,
+        depends_on: Vec::new(),
         storage_var: shared_variables.clone(),
         response_action: ProducerAction::PUBLISHDATA,
     };
@@ -104,10 +115,23 @@ async fn main() -> Result<(), xactor::Error> {
         api_url: String::from("https://api.tdameritrade.com/v1/marketdata/quotes?symbol=[[TDQUOTEINDEX]]"), 
         request_type: ApiRequestType::GET,
         body: None,
-        header: Some((String::from("Authorization"), String::from("Bearer [[TDTOKEN]]"))),
+        headers: Vec::new(),
+        auth: Some(RequestAuth::OAuth2(String::from("TD"))),
+        rate_limit: None,
         //                   sec min hour dayofmonth month  dayofweek
-        cron: String::from("10  */15  9-16   *  *  1-5"),
+        schedule: ScheduleType::Cron(String::from("10  */15  9-16   *  *  Mon-Fri")),
+        calendar: Some(String::from("NYSE")),
+        timezone: Some(String::from("America/New_York")),
         jmespatch_query: String::from("*.{measure_name: symbol, measure_data: {lastPrice: lastPrice, highPrice: highPrice, lowPrice: lowPrice, lastPrice: lastPrice}}"), 
+        format: ResponseFormat::Json,
+        lenient: false,
+        flatten: Flatten::default(),
+        labels: Labels::new(),
+        retry: None,
+        limits: RequestLimits::default(),
+        conditional: ConditionalRequest::default(),
+        pagination: None,
+        depends_on: Vec::new(),
         storage_var: shared_variables.clone(),
         response_action: ProducerAction::PUBLISHDATA,
     };
@@ -1,9 +1,14 @@
 use async_std::task;
 use data_watch::actors::consumer::StdoutConsumer;
 use data_watch::actors::messages::{Stop, WebProducerSchedule};
-use data_watch::actors::producer::{ApiRequestType, ProducerAction};
+use data_watch::actors::producer::{
+    ApiKeyLocation, ApiRequestType, ConditionalRequest, ProducerAction, RequestAuth, RequestLimits,
+    ResponseFormat, RetryPolicy,
+};
 use data_watch::actors::Scheduler;
-use data_watch::SharedVar;
+use data_watch::jsonutility::Flatten;
+use data_watch::schedule::ScheduleType;
+use data_watch::{Labels, SharedVar};
 use std::collections::HashMap;
 use std::env;
 use std::sync::{Arc, RwLock};
@@ -45,13 +50,34 @@ async fn main() -> Result<(), xactor::Error> {
     // Build Request
     let request_message = WebProducerSchedule {
         source_name: String::from("Weather"), 
-        api_url: String::from("https://api.openweathermap.org/data/2.5/weather?q=Houston&units=imperial&appid=[[WEATHER_KEY]]"), 
+        api_url: String::from("https://api.openweathermap.org/data/2.5/weather?q=Houston&units=imperial"), 
         request_type: ApiRequestType::GET,
         body: None,
-        header: None,
-        //                   sec min hour dayofmonth month  dayofweek
-        cron: String::from("0/10  *  *  *  *  *"),
-        jmespatch_query: String::from("merge({measure_name: name},{measure_data: main})"), 
+        headers: Vec::new(),
+        // api key from shared variables added as query parameter appid
+        auth: Some(RequestAuth::ApiKey {
+            location: ApiKeyLocation::Query(String::from("appid")),
+            key_var: String::from("WEATHER_KEY"),
+        }),
+        rate_limit: None,
+        // fixed interval, first request right away
+        schedule: ScheduleType::Interval {
+            every: Duration::from_secs(10),
+            offset: None,
+        },
+        calendar: None,
+        timezone: None,
+        jmespatch_query: String::from("{measure_name: name, measure_data: {main: main, wind: wind, clouds: clouds}, timestamp: dt}"), 
+        // retry failed requests up to 3 times with exponential backoff
+        format: ResponseFormat::Json,
+        lenient: false,
+        flatten: Flatten::default(),
+        labels: Labels::new(),
+        retry: Some(RetryPolicy::default()),
+        limits: RequestLimits::default(),
+        conditional: ConditionalRequest::default(),
+        pagination: None,
+        depends_on: Vec::new(),
         storage_var: shared_variables.clone(),
         response_action: ProducerAction::PUBLISHDATA,
     };
@@ -3,10 +3,12 @@
 //   - maybe keep a vector of names to store in file
 //   - or create a unique routing name from name+description?
 
-use crate::actors::messages::DataResponse;
+use crate::actors::messages::{DataResponse, MeasureValue};
+use crate::Labels;
 use async_std::{
-    fs::File,
-    io::{prelude::WriteExt, BufWriter},
+    fs::{File, OpenOptions},
+    io::{self, prelude::WriteExt, BufWriter},
+    path::Path,
 };
 use async_trait::async_trait;
 use log::info;
@@ -14,7 +16,7 @@ use xactor::*;
 
 /// Outputs to writer which is currently stdout, but could switch in the future
 /// could include state to use with writer
-
+///
 /// DataWriter
 /// Start - subscribed to <DataResponse>
 ///
@@ -22,7 +24,6 @@ use xactor::*;
 /// - print DataTimeseries to screen
 ///
 /// <Ping>
-
 pub struct CsvConsumer {
     writer: BufWriter<File>,
 }
@@ -31,6 +32,16 @@ impl CsvConsumer {
     pub fn new(writer: BufWriter<File>) -> CsvConsumer {
         CsvConsumer { writer }
     }
+
+    /// open csv file to append to, file is created if missing
+    pub async fn open<P: AsRef<Path>>(path: P) -> io::Result<CsvConsumer> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(CsvConsumer::new(BufWriter::new(file)))
+    }
 }
 
 impl Default for CsvConsumer {
@@ -57,20 +68,112 @@ impl Actor for CsvConsumer {
 impl Handler<DataResponse> for CsvConsumer {
     async fn handle(&mut self, _ctx: &mut Context<Self>, msg: DataResponse) {
         // append to csv file stream
-        self.writer
-            .write(
-                format!(
-                    "\"{}\", \"{}\", \"{}\", {}, {}\n",
-                    msg.source_name,
-                    msg.measure_name,
-                    msg.measure_desc,
-                    msg.measure_value,
-                    msg.timestamp,
-                )
-                .as_bytes(),
-            )
-            .await
-            .unwrap();
+        self.writer.write(csv_row(&msg).as_bytes()).await.unwrap();
         self.writer.flush().await.unwrap();
     }
 }
+
+/// row of a data response, labels last so rows written before labels keep their columns
+///
+/// no space after the commas, a csv reader only takes a quote opening a field as quoting
+fn csv_row(msg: &DataResponse) -> String {
+    let text = |text: &str| csv_value(&MeasureValue::Text(text.to_owned()));
+    format!(
+        "{},{},{},{},{},{}\n",
+        text(&msg.source_name),
+        text(&msg.measure_name),
+        text(&msg.measure_desc),
+        csv_value(&msg.measure_value),
+        msg.timestamp,
+        csv_labels(&msg.labels),
+    )
+}
+
+/// numbers and booleans unquoted, text quoted with inner quotes doubled
+fn csv_value(value: &MeasureValue) -> String {
+    match value {
+        MeasureValue::Text(text) => format!("\"{}\"", text.replace('"', "\"\"")),
+        value => value.to_string(),
+    }
+}
+
+/// labels in one quoted column as `name=value` separated by `;`,
+/// with `%`, `;` and `=` in names and values percent encoded
+fn csv_labels(labels: &Labels) -> String {
+    let escape = |text: &str| {
+        text.replace('%', "%25")
+            .replace(';', "%3B")
+            .replace('=', "%3D")
+    };
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}={}", escape(name), escape(value)))
+        .collect();
+    csv_value(&MeasureValue::Text(pairs.join(";")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use percent_encoding::percent_decode_str;
+
+    #[test]
+    fn values_formatted_for_csv() {
+        assert_eq!(csv_value(&MeasureValue::Float(1.5)), "1.5");
+        assert_eq!(
+            csv_value(&MeasureValue::Integer(9007199254740993)),
+            "9007199254740993"
+        );
+        assert_eq!(csv_value(&MeasureValue::Bool(false)), "false");
+        assert_eq!(
+            csv_value(&MeasureValue::from("say \"hi\"")),
+            "\"say \"\"hi\"\"\""
+        );
+    }
+
+    #[test]
+    fn labels_read_back_from_row() {
+        let mut labels = Labels::new();
+        labels.insert(String::from("pair"), String::from("a=b;c,\"d\" 5%"));
+        labels.insert(String::from("x=y"), String::from("1,2"));
+        let row = csv_row(&DataResponse {
+            source_name: String::from("QUOTE"),
+            measure_name: String::from("SPY"),
+            measure_desc: String::from("last"),
+            labels: labels.clone(),
+            measure_value: MeasureValue::Float(412.5),
+            timestamp: 1620000000,
+        });
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(row.as_bytes());
+        let record = reader.records().next().unwrap().unwrap();
+        assert_eq!(record.len(), 6);
+        assert_eq!(&record[0], "QUOTE");
+        assert_eq!(&record[3], "412.5");
+        let decode = |text: &str| percent_decode_str(text).decode_utf8().unwrap().into_owned();
+        let read_back: Labels = record[5]
+            .split(';')
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap();
+                (decode(name), decode(value))
+            })
+            .collect();
+        assert_eq!(read_back, labels);
+    }
+
+    #[test]
+    fn labels_in_one_column() {
+        let mut labels = Labels::new();
+        assert_eq!(csv_labels(&labels), "\"\"");
+        labels.insert(String::from("exchange"), String::from("NYSE"));
+        labels.insert(String::from("currency"), String::from("USD"));
+        assert_eq!(csv_labels(&labels), "\"currency=USD;exchange=NYSE\"");
+        labels.insert(String::from("pair"), String::from("a=b;c,\"d\" 5%"));
+        assert_eq!(
+            csv_labels(&labels),
+            "\"currency=USD;exchange=NYSE;pair=a%3Db%3Bc,\"\"d\"\" 5%25\""
+        );
+    }
+}
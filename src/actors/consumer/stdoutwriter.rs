@@ -1,18 +1,20 @@
-use crate::actors::messages::DataResponse;
+use crate::actors::messages::{DataResponse, MeasureValue, ProducerError};
 use async_trait::async_trait;
 use xactor::*;
 
 /// Outputs to writer which is currently stdout, but could switch in the future
 /// could include state to use with writer
-
+///
 /// DataWriter
-/// Start - subscribed to <DataResponse>
+/// Start - subscribed to <DataResponse> and <ProducerError>
 ///
 /// <DataResponse>
 /// - print DataTimeseries to screen
 ///
+/// <ProducerError>
+/// - print failed request with its response status to stderr
+///
 /// <Ping>
-
 #[derive(Default)]
 pub struct StdoutConsumer;
 
@@ -20,6 +22,7 @@ pub struct StdoutConsumer;
 impl Actor for StdoutConsumer {
     async fn started(&mut self, ctx: &mut Context<Self>) -> Result<()> {
         ctx.subscribe::<DataResponse>().await?;
+        ctx.subscribe::<ProducerError>().await?;
         // println!("Actor::DataWriter started");
         Ok(())
     }
@@ -29,6 +32,36 @@ impl Actor for StdoutConsumer {
 impl Handler<DataResponse> for StdoutConsumer {
     async fn handle(&mut self, _ctx: &mut Context<Self>, msg: DataResponse) {
         // println!("Actor::DataWriter message<DataResponse> received");
-        println!("{:?}", msg);
+        let value = match &msg.measure_value {
+            MeasureValue::Text(text) => format!("{:?}", text),
+            value => value.to_string(),
+        };
+        let labels: Vec<String> = msg
+            .labels
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!(" {{{}}}", labels.join(", "))
+        };
+        println!(
+            "{} {} {}{} = {} at {}",
+            msg.source_name, msg.measure_name, msg.measure_desc, labels, value, msg.timestamp
+        );
+    }
+}
+
+#[async_trait]
+impl Handler<ProducerError> for StdoutConsumer {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: ProducerError) {
+        match &msg.response {
+            Some(response) => eprintln!(
+                "{}: {} (status {} after {:?})",
+                msg.source_name, msg.error, response.status, response.latency
+            ),
+            None => eprintln!("{}: {}", msg.source_name, msg.error),
+        }
     }
 }
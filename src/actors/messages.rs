@@ -1,5 +1,14 @@
-use super::producer::{ApiRequestType, ProducerAction};
-use crate::SharedVar;
+use super::producer::{
+    ApiRequestType, ConditionalRequest, OAuth2Config, Pagination, ProducerAction, RateLimitConfig,
+    RequestAuth, RequestBody, RequestLimits, ResponseFormat, ResponseInfo, RetryPolicy,
+};
+use crate::calendar::Calendar;
+use crate::error::DataWatchError;
+use crate::jsonutility::Flatten;
+use crate::schedule::ScheduleType;
+use crate::{Labels, SharedVar};
+use chrono::{DateTime, Utc};
+use std::fmt;
 use xactor::*;
 
 #[message]
@@ -8,14 +17,64 @@ pub struct WebProducerSchedule {
     pub source_name: String,
     pub api_url: String,
     pub request_type: ApiRequestType,
-    pub body: Option<String>,
-    pub header: Option<(String, String)>,
-    pub cron: String,
+    pub body: Option<RequestBody>,
+    /// `[[VAR]]` is replaced in every header value
+    pub headers: Vec<(String, String)>,
+    /// credentials added to every request, an OAuth2 provider is looked up in the Scheduler
+    pub auth: Option<RequestAuth>,
+    /// name of a limit added with `AddRateLimit`, the limit listing the url host if None
+    pub rate_limit: Option<String>,
+    pub schedule: ScheduleType,
+    /// name of a calendar added to the Scheduler with `AddCalendar`, fire times on its dates are skipped
+    pub calendar: Option<String>,
+    /// IANA timezone like "America/New_York" the cron and calendar are evaluated in, UTC if None
+    pub timezone: Option<String>,
+    /// jmespath expression for `ResponseFormat::Json`
     pub jmespatch_query: String,
+    /// selects the translator reading the response body
+    pub format: ResponseFormat,
+    /// convert numeric strings and leave out values that cannot be published
+    /// in place of failing the response, see `jsonutility::parse_json_data_lenient`
+    pub lenient: bool,
+    /// how objects and arrays in measure_data become descriptors
+    pub flatten: Flatten,
+    /// added to every data point, labels from the response take precedence
+    pub labels: Labels,
+    /// retry failed requests, a single attempt if None
+    pub retry: Option<RetryPolicy>,
+    /// timeouts and response size limit applied to every attempt
+    pub limits: RequestLimits,
+    /// request only changed data using the validators of the last response
+    pub conditional: ConditionalRequest,
+    /// request further pages and merge their results, a single page if None
+    pub pagination: Option<Pagination>,
+    /// hold the first run until these are met, a 401 response re-runs the schedules depended on
+    pub depends_on: Vec<Dependency>,
     pub storage_var: SharedVar,
     pub response_action: ProducerAction,
 }
 
+impl WebProducerSchedule {
+    /// source names of the schedules depended on
+    pub fn schedule_dependencies(&self) -> impl Iterator<Item = &String> {
+        self.depends_on
+            .iter()
+            .filter_map(|dependency| match dependency {
+                Dependency::Schedule(name) => Some(name),
+                Dependency::Variable(_) => None,
+            })
+    }
+}
+
+/// Requirement a schedule waits on before its first run
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dependency {
+    /// a schedule with this source_name has completed a run
+    Schedule(String),
+    /// a variable with this name is in the shared variables
+    Variable(String),
+}
+
 #[message]
 #[derive(Debug, Clone)]
 pub struct Refresh;
@@ -24,15 +83,167 @@ pub struct Refresh;
 #[derive(Debug, Clone)]
 pub struct Run;
 
+/// Run request right away without changing the schedule
+#[message]
+#[derive(Debug, Clone)]
+pub struct RunNow;
+
+/// Sent by a producer to the Scheduler after every request, `error` is None on success
+#[message]
+#[derive(Debug, Clone)]
+pub struct RunComplete {
+    pub id: u64,
+    pub error: Option<DataWatchError>,
+}
+
+#[message(result = "ProducerStats")]
+#[derive(Debug, Clone)]
+pub struct Stats;
+
+#[derive(Debug, Clone, Default)]
+pub struct ProducerStats {
+    pub next_run: Option<DateTime<Utc>>,
+    pub retries: u64,
+    /// response to the last request attempt, None before the first or after one without response
+    pub last_response: Option<ResponseInfo>,
+}
+
+/// Sent by a producer to the Scheduler when its next run, retries or last response change
+#[message]
+#[derive(Debug, Clone)]
+pub struct StatsUpdate {
+    pub id: u64,
+    pub stats: ProducerStats,
+}
+
 #[message]
 #[derive(Debug, Clone)]
 pub struct DataResponse {
     pub source_name: String,
     pub measure_name: String,
     pub measure_desc: String,
-    pub measure_value: f64,
+    /// extra dimensions of the series, such as exchange or currency
+    pub labels: Labels,
+    pub measure_value: MeasureValue,
+    pub timestamp: u64,
+}
+
+/// Value of a measure as found in the response
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeasureValue {
+    Float(f64),
+    /// whole json numbers that fit in an i64, kept exact
+    Integer(i64),
+    Bool(bool),
+    Text(String),
+}
+
+impl MeasureValue {
+    /// value as a number, booleans are 1 or 0 and text is None
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            MeasureValue::Float(value) => Some(*value),
+            MeasureValue::Integer(value) => Some(*value as f64),
+            MeasureValue::Bool(value) => Some(if *value { 1.0 } else { 0.0 }),
+            MeasureValue::Text(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for MeasureValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeasureValue::Float(value) => write!(f, "{}", value),
+            MeasureValue::Integer(value) => write!(f, "{}", value),
+            MeasureValue::Bool(value) => write!(f, "{}", value),
+            MeasureValue::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<f64> for MeasureValue {
+    fn from(value: f64) -> Self {
+        MeasureValue::Float(value)
+    }
+}
+
+impl From<i64> for MeasureValue {
+    fn from(value: i64) -> Self {
+        MeasureValue::Integer(value)
+    }
+}
+
+impl From<bool> for MeasureValue {
+    fn from(value: bool) -> Self {
+        MeasureValue::Bool(value)
+    }
+}
+
+impl From<&str> for MeasureValue {
+    fn from(value: &str) -> Self {
+        MeasureValue::Text(value.to_owned())
+    }
+}
+
+/// Published on the broker for a failed request or parse, subscribe like `DataResponse`
+#[message]
+#[derive(Debug, Clone)]
+pub struct ProducerError {
+    pub source_name: String,
+    pub error: DataWatchError,
+    /// status, headers and latency when a response was received
+    pub response: Option<ResponseInfo>,
     pub timestamp: u64,
 }
 
 #[message]
 pub struct Stop;
+
+/// Add or replace a named calendar that schedules can reference to skip dates
+#[message]
+#[derive(Debug, Clone)]
+pub struct AddCalendar(pub Calendar);
+
+/// Add or replace a named OAuth2 provider that schedules can use with `RequestAuth::OAuth2`
+#[message]
+#[derive(Debug, Clone)]
+pub struct AddAuthProvider(pub OAuth2Config);
+
+/// Add or replace a named rate limit shared by the schedules using it
+#[message]
+#[derive(Debug, Clone)]
+pub struct AddRateLimit(pub RateLimitConfig);
+
+/// Stop a running schedule by the id shown in `ListSchedule` or by `source_name`
+/// Stopping by `source_name` stops every schedule with that name
+#[message]
+#[derive(Debug, Clone)]
+pub enum StopSchedule {
+    Id(u64),
+    SourceName(String),
+}
+
+#[message(result = "Vec<ScheduleStatus>")]
+#[derive(Debug, Clone)]
+pub struct ListSchedule;
+
+#[derive(Debug, Clone)]
+pub struct ScheduleStatus {
+    pub id: u64,
+    pub source_name: String,
+    pub api_url: String,
+    pub next_run: Option<DateTime<Utc>>,
+    pub retries: u64,
+    pub last_response: Option<ResponseInfo>,
+    /// first run is held until dependencies are met
+    pub waiting: bool,
+}
+
+#[message(result = "Pong")]
+#[derive(Debug, Clone)]
+pub struct Ping;
+
+#[derive(Debug, Clone)]
+pub struct Pong {
+    pub active_schedules: usize,
+}
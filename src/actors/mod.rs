@@ -3,4 +3,4 @@ pub mod messages;
 pub mod producer;
 mod scheduler;
 
-pub use scheduler::Scheduler;
+pub use scheduler::{dependency_order, Scheduler};
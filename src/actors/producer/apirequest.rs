@@ -1,27 +1,485 @@
+use crate::actors::producer::client::{client, ConnectTimeout};
+use crate::actors::producer::fixtures::{fixture_mode, FixtureRequest};
 use crate::actors::producer::ApiRequestType;
-use http_types::mime;
+use crate::error::{DataWatchError, DataWatchResult};
+use crate::{varstore, SharedVar};
+use async_std::future::timeout;
+use async_std::io::ReadExt;
+use encoding_rs::{Encoding, UTF_8};
+use http_types::{mime, Mime};
 use log::debug;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use surf::Url;
 
-/// function makes an api request based on configuration
+/// Body sent with a request, `[[VAR]]` is replaced in the text before sending
+#[derive(Debug, Clone)]
+pub enum RequestBody {
+    /// url encoded form, variables are url encoded
+    Form(String),
+    /// json text sent as application/json, variables are inserted as is
+    Json(String),
+    /// text sent with an explicit content type, variables are inserted as is
+    Raw {
+        content_type: String,
+        content: String,
+    },
+}
+
+impl RequestBody {
+    /// content type sent with the body
+    pub fn content_type(&self) -> DataWatchResult<Mime> {
+        match self {
+            RequestBody::Form(_) => Ok(mime::FORM),
+            RequestBody::Json(_) => Ok(mime::JSON),
+            RequestBody::Raw { content_type, .. } => Mime::from_str(content_type).map_err(|_| {
+                DataWatchError::Config(format!("invalid content type {}", content_type))
+            }),
+        }
+    }
+
+    /// body text with variables from shared storage
+    pub fn content(&self, storage_var: &SharedVar) -> String {
+        match self {
+            RequestBody::Form(text) => varstore::swap_variable(storage_var, text, true),
+            RequestBody::Json(text) | RequestBody::Raw { content: text, .. } => {
+                varstore::swap_variable(storage_var, text, false)
+            }
+        }
+    }
+}
+
+/// Limits on a single request attempt, a limit that is exceeded fails the request
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestLimits {
+    /// time allowed to connect to the host, None for the 300 second default of the client
+    pub connect_timeout: Option<Duration>,
+    /// time allowed until the response headers arrive, None for no limit
+    pub header_timeout: Option<Duration>,
+    /// time allowed for the whole request including the response body, None for no limit
+    pub total_timeout: Option<Duration>,
+    /// largest response body accepted in bytes, None for no limit
+    pub max_body_size: Option<u64>,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        RequestLimits {
+            connect_timeout: Some(Duration::from_secs(10)),
+            header_timeout: Some(Duration::from_secs(30)),
+            total_timeout: Some(Duration::from_secs(60)),
+            max_body_size: Some(10 * 1024 * 1024),
+        }
+    }
+}
+
+/// Status, headers and latency of a response
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseInfo {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// time from sending the request until the response headers arrived
+    pub latency: Duration,
+}
+
+impl ResponseInfo {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// value of the header named `name`, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Response to `request_api`, the body is only read for a success status
+#[derive(Debug, Clone)]
+pub struct ApiResponse {
+    pub info: ResponseInfo,
+    pub body: String,
+}
+
+impl ApiResponse {
+    /// body of a success response, any other status is returned as `DataWatchError::Status`
+    pub fn into_body(self) -> DataWatchResult<String> {
+        if self.info.is_success() {
+            Ok(self.body)
+        } else {
+            Err(DataWatchError::Status(self.info.status))
+        }
+    }
+}
+
+/// function makes an api request based on configuration using the shared client
+/// a response with any status is returned, errors are for requests without a complete response
+/// `secrets` names the query parameters and headers holding credentials, for fixtures
 pub async fn request_api(
     request_type: &ApiRequestType,
     api_url: &str,
-    body: &str,
-    header: Option<(&str, String)>,
-) -> String {
+    body: Option<(String, Mime)>,
+    headers: &[(String, String)],
+    limits: &RequestLimits,
+    secrets: &[&str],
+) -> DataWatchResult<ApiResponse> {
+    // replayed responses skip the network, recorded ones are saved once complete
+    let fixtures = fixture_mode();
+    let fixture_body = fixtures.and(body.as_ref().map(|(content, _)| content.clone()));
+    let fixture_request = FixtureRequest {
+        method: request_type,
+        url: api_url,
+        body: fixture_body.as_deref(),
+        secrets,
+    };
+    if let Some(replayed) = fixtures {
+        if let Some(response) = replayed.replay(&fixture_request).await {
+            return response;
+        }
+    }
+
+    let client = client();
     let mut request = match request_type {
-        ApiRequestType::GET => surf::get(api_url),
-        ApiRequestType::POST => surf::post(api_url).body(body).content_type(mime::FORM),
+        ApiRequestType::GET => client.get(api_url),
+        ApiRequestType::POST => client.post(api_url),
+        ApiRequestType::PUT => client.put(api_url),
+        ApiRequestType::PATCH => client.patch(api_url),
+        ApiRequestType::DELETE => client.delete(api_url),
+        ApiRequestType::HEAD => client.head(api_url),
     };
 
-    // set header
-    if let Some((key, value)) = header {
-        request = request.header(key, value);
+    if let Some((content, content_type)) = body {
+        request = request.body(content).content_type(content_type);
+    }
+
+    // set headers after the body so a Content-Type header wins
+    for (key, value) in headers {
+        request = request.header(key.as_str(), value.as_str());
+    }
+    let mut request = request.build();
+    if let Some(limit) = limits.connect_timeout {
+        request.set_ext(ConnectTimeout(limit));
+    }
+
+    let exchange = async {
+        let sent = Instant::now();
+        let mut response = within(
+            limits.header_timeout,
+            DataWatchError::HeaderTimeout,
+            async {
+                client
+                    .send(request)
+                    .await
+                    .map_err(|e| connect_error(e, limits.connect_timeout))
+            },
+        )
+        .await?;
+        let info = ResponseInfo {
+            status: response.status().into(),
+            headers: response
+                .iter()
+                .map(|(name, values)| {
+                    let values: Vec<&str> = values.iter().map(|value| value.as_str()).collect();
+                    (name.to_string(), values.join(", "))
+                })
+                .collect(),
+            latency: sent.elapsed(),
+        };
+        let body = if info.is_success() {
+            read_body(&mut response, limits.max_body_size).await?
+        } else {
+            String::new()
+        };
+        Ok(ApiResponse { info, body })
     };
+    let response = within(limits.total_timeout, DataWatchError::Timeout, exchange).await?;
 
-    let response = request.recv_string().await.unwrap();
+    debug!(
+        "Response received: status {} after {:?}, {} bytes",
+        response.info.status,
+        response.info.latency,
+        response.body.len()
+    );
 
-    debug!("Response received: {:?}", &response);
+    if let Some(recorder) = fixtures {
+        recorder.record(&fixture_request, &response).await;
+    }
+    Ok(response)
+}
 
+/// `url` with query parameter `name` set to `value`, replacing an existing value
+pub(crate) fn with_query_param(url: &str, name: &str, value: &str) -> DataWatchResult<String> {
+    let mut url =
+        Url::parse(url).map_err(|e| DataWatchError::Request(format!("invalid url: {}", e)))?;
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| key != name)
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair(name, value);
+    Ok(url.to_string())
+}
+
+/// `DataWatchError::ConnectTimeout` for a request that timed out connecting,
+/// the client has no other timeout
+fn connect_error(error: surf::Error, connect_timeout: Option<Duration>) -> DataWatchError {
+    match (error.downcast_ref::<isahc::Error>(), connect_timeout) {
+        (Some(isahc::Error::Timeout), Some(limit)) => DataWatchError::ConnectTimeout(limit),
+        _ => DataWatchError::from(error),
+    }
+}
+
+/// run `future` failing with `error` if it takes longer than `limit`
+async fn within<T>(
+    limit: Option<Duration>,
+    error: fn(Duration) -> DataWatchError,
+    future: impl Future<Output = DataWatchResult<T>>,
+) -> DataWatchResult<T> {
+    match limit {
+        Some(limit) => timeout(limit, future)
+            .await
+            .unwrap_or_else(|_| Err(error(limit))),
+        None => future.await,
+    }
+}
+
+/// read response body as text decoded with the charset of its content type,
+/// stops reading once over `max_size` bytes
+async fn read_body(
+    response: &mut surf::Response,
+    max_size: Option<u64>,
+) -> DataWatchResult<String> {
+    let max_size = max_size.unwrap_or(u64::MAX);
+    if response
+        .len()
+        .is_some_and(|length| length as u64 > max_size)
+    {
+        return Err(DataWatchError::ResponseTooLarge(max_size));
+    }
+    let mut body = Vec::new();
     response
+        .take(max_size.saturating_add(1))
+        .read_to_end(&mut body)
+        .await
+        .map_err(|e| DataWatchError::Request(e.to_string()))?;
+    if body.len() as u64 > max_size {
+        return Err(DataWatchError::ResponseTooLarge(max_size));
+    }
+    let charset = response
+        .content_type()
+        .and_then(|mime| mime.param("charset").map(|charset| charset.to_string()));
+    Ok(decode_body(&body, charset.as_deref()))
+}
+
+/// `body` as text in `charset`, UTF-8 when missing or unknown,
+/// bytes that are not valid in the charset are replaced
+fn decode_body(body: &[u8], charset: Option<&str>) -> String {
+    let encoding = charset
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8);
+    let (text, _, malformed) = encoding.decode(body);
+    if malformed {
+        debug!("Response body is not valid {}", encoding.name());
+    }
+    text.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testserver::{TestResponse, TestServer};
+    use async_std::io::prelude::WriteExt;
+    use async_std::net::TcpListener;
+    use async_std::task;
+    use socket2::{Domain, SockAddr, Socket, Type};
+    use std::collections::HashMap;
+    use std::net::{SocketAddr, TcpStream};
+    use std::sync::{Arc, RwLock};
+
+    /// server that accepts one connection, writes `reply` and then stalls
+    async fn stalled_server(reply: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        task::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(reply).await.unwrap();
+            task::sleep(Duration::from_secs(10)).await;
+        });
+        url
+    }
+
+    /// url of a listener whose backlog is full, so a connection attempt goes unanswered
+    /// as it would to an address that cannot be routed, keep the sockets until done
+    fn unanswered_server() -> (String, Socket, TcpStream) {
+        let listener = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+        let any_port: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        listener.bind(&SockAddr::from(any_port)).unwrap();
+        listener.listen(0).unwrap();
+        let addr = listener.local_addr().unwrap().as_socket().unwrap();
+        let queued = TcpStream::connect(addr).unwrap();
+        (format!("http://{}", addr), listener, queued)
+    }
+
+    async fn get(url: &str, limits: RequestLimits) -> DataWatchResult<String> {
+        request_api(&ApiRequestType::GET, url, None, &[], &limits, &[])
+            .await
+            .and_then(ApiResponse::into_body)
+    }
+
+    #[async_std::test]
+    async fn request_with_json_body_and_headers() {
+        let server = TestServer::start(vec![TestResponse::ok("{}")]).await;
+        let body = RequestBody::Json(String::from(r#"{"symbol": "SPY"}"#));
+        let headers = vec![
+            (String::from("Accept"), String::from("application/json")),
+            (String::from("User-Agent"), String::from("data-watch")),
+        ];
+        let response = request_api(
+            &ApiRequestType::PUT,
+            &server.url,
+            Some((
+                String::from(r#"{"symbol": "SPY"}"#),
+                body.content_type().unwrap(),
+            )),
+            &headers,
+            &RequestLimits::default(),
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.body, "{}");
+
+        let request = &server.requests()[0];
+        assert!(request.head.starts_with("PUT / "));
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.header("accept"), Some("application/json"));
+        assert_eq!(request.header("user-agent"), Some("data-watch"));
+        assert_eq!(request.body, r#"{"symbol": "SPY"}"#);
+    }
+
+    #[async_std::test]
+    async fn error_status_with_headers() {
+        let server = TestServer::start(vec![TestResponse::status(429)]).await;
+        let response = request_api(
+            &ApiRequestType::GET,
+            &server.url,
+            None,
+            &[],
+            &RequestLimits::default(),
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.info.status, 429);
+        assert!(response
+            .info
+            .headers
+            .contains(&(String::from("content-length"), String::from("0"))));
+        assert!(response.info.latency < Duration::from_secs(5));
+        assert_eq!(response.into_body(), Err(DataWatchError::Status(429)));
+    }
+
+    #[test]
+    fn body_content_and_type() {
+        let storage_var = Arc::new(RwLock::new(HashMap::new()));
+        storage_var
+            .write()
+            .unwrap()
+            .insert(String::from("TOKEN"), String::from("a b"));
+        let form = RequestBody::Form(String::from("token=[[TOKEN]]"));
+        assert_eq!(form.content(&storage_var), "token=a%20b");
+        let raw = RequestBody::Raw {
+            content_type: String::from("text/plain"),
+            content: String::from("token [[TOKEN]]"),
+        };
+        assert_eq!(raw.content(&storage_var), "token a b");
+        assert_eq!(raw.content_type().unwrap().essence(), "text/plain");
+        let invalid = RequestBody::Raw {
+            content_type: String::from("not a type"),
+            content: String::new(),
+        };
+        assert!(invalid.content_type().is_err());
+    }
+
+    #[async_std::test]
+    async fn connect_timeout_without_connection() {
+        let (url, _listener, _queued) = unanswered_server();
+        let limits = RequestLimits {
+            connect_timeout: Some(Duration::from_millis(200)),
+            header_timeout: None,
+            total_timeout: None,
+            max_body_size: None,
+        };
+        assert_eq!(
+            get(&url, limits).await,
+            Err(DataWatchError::ConnectTimeout(Duration::from_millis(200)))
+        );
+    }
+
+    #[async_std::test]
+    async fn header_timeout_without_response() {
+        let url = stalled_server(b"").await;
+        let limits = RequestLimits {
+            header_timeout: Some(Duration::from_millis(200)),
+            ..RequestLimits::default()
+        };
+        assert_eq!(
+            get(&url, limits).await,
+            Err(DataWatchError::HeaderTimeout(Duration::from_millis(200)))
+        );
+    }
+
+    #[async_std::test]
+    async fn total_timeout_on_slow_body() {
+        let url = stalled_server(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\npartial").await;
+        let limits = RequestLimits {
+            header_timeout: Some(Duration::from_secs(5)),
+            total_timeout: Some(Duration::from_millis(300)),
+            max_body_size: None,
+            ..RequestLimits::default()
+        };
+        assert_eq!(
+            get(&url, limits).await,
+            Err(DataWatchError::Timeout(Duration::from_millis(300)))
+        );
+    }
+
+    #[async_std::test]
+    async fn body_decoded_with_charset() {
+        let url = stalled_server(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=iso-8859-1\r\n\
+              Content-Length: 9\r\n\r\nSPY 1.5\xa0\xe9",
+        )
+        .await;
+        assert_eq!(
+            get(&url, RequestLimits::default()).await.unwrap(),
+            "SPY 1.5\u{a0}\u{e9}"
+        );
+        assert_eq!(decode_body(b"caf\xe9", None), "caf\u{fffd}");
+        assert_eq!(decode_body(b"caf\xc3\xa9", Some("unknown")), "caf\u{e9}");
+    }
+
+    #[async_std::test]
+    async fn response_over_size_limit() {
+        let server = TestServer::start(vec![TestResponse::ok("0123456789")]).await;
+        let limits = RequestLimits {
+            max_body_size: Some(9),
+            ..RequestLimits::default()
+        };
+        assert_eq!(
+            get(&server.url, limits.clone()).await,
+            Err(DataWatchError::ResponseTooLarge(9))
+        );
+        let limits = RequestLimits {
+            max_body_size: Some(10),
+            ..limits
+        };
+        assert_eq!(get(&server.url, limits).await.unwrap(), "0123456789");
+    }
 }
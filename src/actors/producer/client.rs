@@ -0,0 +1,135 @@
+use crate::error::{DataWatchError, DataWatchResult};
+use async_std::io::BufReader;
+use async_trait::async_trait;
+use http_client::{Body, Error, HttpClient, Request, Response};
+use isahc::config::Configurable;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static CLIENT: OnceLock<surf::Client> = OnceLock::new();
+
+/// Settings for the HTTP client shared by every WebProducer
+///
+/// Connections are pooled and kept alive between requests, a request that would go over
+/// a connection limit waits for a connection to free up
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientConfig {
+    /// most open connections in total, 0 for no limit
+    pub max_connections: usize,
+    /// most open connections to one host, 0 for no limit
+    pub max_connections_per_host: usize,
+    /// idle connections kept for reuse, 0 closes every connection after its request
+    pub connection_cache_size: usize,
+    /// how long an idle connection is kept for reuse
+    pub idle_timeout: Duration,
+    /// interval of tcp keep-alive probes on open connections, None to disable
+    pub tcp_keepalive: Option<Duration>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            max_connections: 0,
+            max_connections_per_host: 6,
+            connection_cache_size: 100,
+            idle_timeout: Duration::from_secs(90),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+/// set up the shared client, call once at startup before any schedule runs
+///
+/// fails if the client is already configured or has been used with default settings
+pub fn configure_client(config: &ClientConfig) -> DataWatchResult<()> {
+    let client = build_client(config)?;
+    CLIENT
+        .set(client)
+        .map_err(|_| DataWatchError::Config(String::from("http client is already in use")))
+}
+
+/// client shared by every request, default settings unless configured first
+pub(crate) fn client() -> &'static surf::Client {
+    CLIENT.get_or_init(|| {
+        build_client(&ClientConfig::default()).expect("default http client settings are valid")
+    })
+}
+
+/// Time allowed to connect to the host of a request, set as a request extension
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ConnectTimeout(pub Duration);
+
+/// isahc client sending every request over the shared connection pool
+/// with the `ConnectTimeout` of the request
+#[derive(Debug)]
+struct PooledClient(isahc::HttpClient);
+
+#[async_trait]
+impl HttpClient for PooledClient {
+    async fn send(&self, mut req: Request) -> Result<Response, Error> {
+        let mut builder = isahc::http::Request::builder()
+            .uri(req.url().as_str())
+            .method(req.method().to_string().as_str());
+        if let Some(ConnectTimeout(limit)) = req.ext().get::<ConnectTimeout>() {
+            builder = builder.connect_timeout(*limit);
+        }
+        for (name, values) in req.iter() {
+            for value in values {
+                builder = builder.header(name.as_str(), value.as_str());
+            }
+        }
+        let body = req.take_body();
+        let body = match body.len() {
+            Some(len) => isahc::Body::from_reader_sized(body, len as u64),
+            None => isahc::Body::from_reader(body),
+        };
+        let request = builder.body(body)?;
+
+        let (parts, body) = self.0.send_async(request).await?.into_parts();
+        let mut response = Response::new(parts.status.as_u16());
+        for (name, value) in &parts.headers {
+            response.append_header(name.as_str(), value.to_str()?);
+        }
+        response.set_body(Body::from_reader(BufReader::new(body), None));
+        Ok(response)
+    }
+}
+
+fn build_client(config: &ClientConfig) -> DataWatchResult<surf::Client> {
+    let mut builder = isahc::HttpClient::builder()
+        .max_connections(config.max_connections)
+        .max_connections_per_host(config.max_connections_per_host)
+        .connection_cache_size(config.connection_cache_size)
+        .connection_cache_ttl(config.idle_timeout);
+    if let Some(interval) = config.tcp_keepalive {
+        builder = builder.tcp_keepalive(interval);
+    }
+    let client = builder
+        .build()
+        .map_err(|e| DataWatchError::Config(format!("http client: {}", e)))?;
+    Ok(surf::Client::with_http_client(PooledClient(client)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configure_only_once() {
+        // first call may lose to another test that already made a request
+        configure_client(&ClientConfig::default()).ok();
+        assert!(configure_client(&ClientConfig::default()).is_err());
+    }
+
+    #[test]
+    fn build_with_limits() {
+        let config = ClientConfig {
+            max_connections: 10,
+            max_connections_per_host: 2,
+            connection_cache_size: 0,
+            idle_timeout: Duration::from_secs(5),
+            tcp_keepalive: None,
+        };
+        assert!(build_client(&config).is_ok());
+    }
+}
@@ -0,0 +1,397 @@
+use crate::actors::producer::apirequest::{ApiResponse, ResponseInfo};
+use crate::actors::producer::ApiRequestType;
+use crate::error::{DataWatchError, DataWatchResult};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use surf::Url;
+
+static FIXTURES: OnceLock<FixtureMode> = OnceLock::new();
+
+/// query parameters, form and json fields and response headers saved as `REDACTED`
+/// along with the secrets named by the auth of a request
+const SECRET_FIELDS: &[&str] = &[
+    "client_secret",
+    "refresh_token",
+    "access_token",
+    "password",
+    "authorization",
+    "set-cookie",
+];
+pub(crate) const REDACTED: &str = "REDACTED";
+
+/// Saves every response to a directory or serves saved responses in place of the network
+///
+/// a fixture is a json file per method, url and request body holding the request and
+/// the response status, headers and body. Credentials in query parameters, form and json
+/// fields and headers are saved as `REDACTED` and left out of the file name, a replayed
+/// request matches its fixture with them redacted the same way so it needs no credentials
+#[derive(Debug, Clone, PartialEq)]
+pub enum FixtureMode {
+    /// send requests and save each response, replacing an earlier fixture of the request
+    Record(PathBuf),
+    /// answer requests from saved fixtures only, a request without one fails
+    Replay(PathBuf),
+}
+
+/// Request a fixture is saved for
+pub(crate) struct FixtureRequest<'a> {
+    pub method: &'a ApiRequestType,
+    pub url: &'a str,
+    pub body: Option<&'a str>,
+    /// query parameters and headers holding credentials, see `RequestAuth::secret_names`
+    pub secrets: &'a [&'a str],
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Fixture {
+    method: String,
+    url: String,
+    request_body: Option<String>,
+    status: u16,
+    headers: Vec<(String, String)>,
+    latency_ms: u64,
+    body: String,
+}
+
+/// record or replay every request, call once at startup before any schedule runs
+///
+/// the record directory is created when missing
+pub fn configure_fixtures(mode: FixtureMode) -> DataWatchResult<()> {
+    match &mode {
+        FixtureMode::Record(dir) => fs::create_dir_all(dir)
+            .map_err(|e| DataWatchError::Config(format!("fixtures {}: {}", dir.display(), e)))?,
+        FixtureMode::Replay(dir) if !dir.is_dir() => {
+            return Err(DataWatchError::Config(format!(
+                "fixtures {} is not a directory",
+                dir.display()
+            )))
+        }
+        FixtureMode::Replay(_) => {}
+    }
+    FIXTURES
+        .set(mode)
+        .map_err(|_| DataWatchError::Config(String::from("fixtures are already configured")))
+}
+
+/// configured fixture mode, None to use the network only
+pub(crate) fn fixture_mode() -> Option<&'static FixtureMode> {
+    FIXTURES.get()
+}
+
+impl FixtureMode {
+    /// response from the fixture of `request`, None when recording
+    pub(crate) async fn replay(
+        &self,
+        request: &FixtureRequest<'_>,
+    ) -> Option<DataWatchResult<ApiResponse>> {
+        let dir = match self {
+            FixtureMode::Replay(dir) => dir,
+            FixtureMode::Record(_) => return None,
+        };
+        let name = fixture_name(&request.redacted());
+        debug!("Replaying fixture {}", name);
+        let fixture = match async_std::fs::read_to_string(dir.join(&name)).await {
+            Ok(text) => serde_json::from_str::<Fixture>(&text)
+                .map_err(|e| DataWatchError::Json(format!("fixture {}: {}", name, e))),
+            Err(_) => Err(DataWatchError::Request(format!("no fixture {}", name))),
+        };
+        Some(fixture.map(|fixture| ApiResponse {
+            info: ResponseInfo {
+                status: fixture.status,
+                headers: fixture.headers,
+                latency: Duration::from_millis(fixture.latency_ms),
+            },
+            body: fixture.body,
+        }))
+    }
+
+    /// save `response` as the fixture of `request` when recording,
+    /// a fixture that cannot be saved is logged and the request goes on
+    pub(crate) async fn record(&self, request: &FixtureRequest<'_>, response: &ApiResponse) {
+        let dir = match self {
+            FixtureMode::Record(dir) => dir,
+            FixtureMode::Replay(_) => return,
+        };
+        let secrets = request.secrets;
+        let request = request.redacted();
+        let name = fixture_name(&request);
+        let fixture = Fixture {
+            method: request.method,
+            url: request.url,
+            request_body: request.body,
+            status: response.info.status,
+            headers: response
+                .info
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    let value = if is_secret(name, secrets) {
+                        REDACTED
+                    } else {
+                        value
+                    };
+                    (name.clone(), value.to_owned())
+                })
+                .collect(),
+            latency_ms: response.info.latency.as_millis() as u64,
+            body: redact_body(&response.body, secrets),
+        };
+        if let Err(e) = write_fixture(&dir.join(&name), &fixture).await {
+            warn!("Fixture {} not saved: {}", name, e);
+        } else {
+            debug!("Recorded fixture {}", name);
+        }
+    }
+}
+
+async fn write_fixture(path: &Path, fixture: &Fixture) -> std::io::Result<()> {
+    let text = serde_json::to_string_pretty(fixture)?;
+    async_std::fs::write(path, text).await
+}
+
+impl FixtureRequest<'_> {
+    /// request as saved, with its secrets redacted
+    fn redacted(&self) -> RedactedRequest {
+        RedactedRequest {
+            method: format!("{:?}", self.method),
+            url: redact_url(self.url, self.secrets),
+            body: self.body.map(|body| redact_body(body, self.secrets)),
+        }
+    }
+}
+
+/// `FixtureRequest` with secret values replaced by `REDACTED`
+struct RedactedRequest {
+    method: String,
+    url: String,
+    body: Option<String>,
+}
+
+fn is_secret(name: &str, secrets: &[&str]) -> bool {
+    SECRET_FIELDS
+        .iter()
+        .chain(secrets)
+        .any(|secret| secret.eq_ignore_ascii_case(name))
+}
+
+fn redact_url(url: &str, secrets: &[&str]) -> String {
+    let mut parsed = match Url::parse(url) {
+        Ok(parsed)
+            if parsed
+                .query_pairs()
+                .any(|(name, _)| is_secret(&name, secrets)) =>
+        {
+            parsed
+        }
+        _ => return url.to_owned(),
+    };
+    let pairs: Vec<(String, String)> = parsed
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if is_secret(&name, secrets) {
+                REDACTED
+            } else {
+                &value
+            };
+            (name.to_string(), value.to_owned())
+        })
+        .collect();
+    parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    parsed.to_string()
+}
+
+/// secret fields of a json or form body redacted, any other body unchanged
+fn redact_body(body: &str, secrets: &[&str]) -> String {
+    if let Ok(mut json) = serde_json::from_str::<serde_json::Value>(body) {
+        return if redact_json(&mut json, secrets) {
+            json.to_string()
+        } else {
+            body.to_owned()
+        };
+    }
+    body.split('&')
+        .map(|field| match field.split_once('=') {
+            Some((name, _)) if is_secret(name, secrets) => format!("{}={}", name, REDACTED),
+            _ => field.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// true when a secret field in `value` or nested in it was redacted
+fn redact_json(value: &mut serde_json::Value, secrets: &[&str]) -> bool {
+    let mut redacted = false;
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if is_secret(name, secrets) {
+                    *field = serde_json::Value::from(REDACTED);
+                    redacted = true;
+                } else {
+                    redacted |= redact_json(field, secrets);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                redacted |= redact_json(item, secrets);
+            }
+        }
+        _ => {}
+    }
+    redacted
+}
+
+/// file name from method, host and a hash of method, url and body that is stable between runs
+fn fixture_name(request: &RedactedRequest) -> String {
+    let method = &request.method;
+    let host: String = Url::parse(&request.url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '-')
+        .collect();
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let key = [
+        method.as_str(),
+        request.url.as_str(),
+        request.body.as_deref().unwrap_or(""),
+    ]
+    .join("\n");
+    for byte in key.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{}-{}-{:016x}.json", method, host, hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("data-watch-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[async_std::test]
+    async fn recorded_response_replayed() {
+        let dir = fixture_dir("fixtures");
+        let request = FixtureRequest {
+            method: &ApiRequestType::POST,
+            url: "https://api.example.com/quotes?symbol=SPY",
+            body: Some("a=1"),
+            secrets: &[],
+        };
+        let response = ApiResponse {
+            info: ResponseInfo {
+                status: 200,
+                headers: vec![(String::from("etag"), String::from("\"v1\""))],
+                latency: Duration::from_millis(120),
+            },
+            body: String::from(r#"{"price": 1.5}"#),
+        };
+        assert!(FixtureMode::Record(dir.clone())
+            .replay(&request)
+            .await
+            .is_none());
+        FixtureMode::Record(dir.clone())
+            .record(&request, &response)
+            .await;
+
+        let replayed = FixtureMode::Replay(dir.clone())
+            .replay(&request)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(replayed.info, response.info);
+        assert_eq!(replayed.body, response.body);
+
+        let other_body = FixtureRequest {
+            body: Some("a=2"),
+            ..request
+        };
+        let missing = FixtureMode::Replay(dir.clone())
+            .replay(&other_body)
+            .await
+            .unwrap();
+        assert!(matches!(missing, Err(DataWatchError::Request(_))));
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[async_std::test]
+    async fn secrets_redacted_in_fixtures() {
+        let dir = fixture_dir("fixtures-secrets");
+        let request = FixtureRequest {
+            method: &ApiRequestType::POST,
+            url: "https://api.example.com/token?appid=key123&q=Houston",
+            body: Some("grant_type=refresh_token&refresh_token=old456&client_secret=shh789"),
+            secrets: &["appid", "X-Session"],
+        };
+        let response = ApiResponse {
+            info: ResponseInfo {
+                status: 200,
+                headers: vec![
+                    (String::from("set-cookie"), String::from("session=abc")),
+                    (String::from("x-session"), String::from("def345")),
+                ],
+                latency: Duration::from_millis(120),
+            },
+            body: String::from(r#"{"access_token": "new012", "expires_in": 1800}"#),
+        };
+        FixtureMode::Record(dir.clone())
+            .record(&request, &response)
+            .await;
+
+        let mut files = fs::read_dir(&dir).unwrap();
+        let path = files.next().unwrap().unwrap().path();
+        let saved = fs::read_to_string(&path).unwrap();
+        for secret in &["key123", "old456", "shh789", "abc", "def345", "new012"] {
+            assert!(!saved.contains(secret), "{} saved", secret);
+            assert!(!path.to_string_lossy().contains(secret));
+        }
+        assert!(saved.contains("q=Houston"));
+        assert!(saved.contains("grant_type=refresh_token"));
+
+        let replayed = FixtureMode::Replay(dir.clone())
+            .replay(&request)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            replayed.body,
+            r#"{"access_token":"REDACTED","expires_in":1800}"#
+        );
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn fixture_names_are_stable() {
+        let request = FixtureRequest {
+            method: &ApiRequestType::GET,
+            url: "https://api.pro.coinbase.com/products/BTC-USD/ticker",
+            body: None,
+            secrets: &[],
+        };
+        let name = fixture_name(&request.redacted());
+        assert!(name.starts_with("GET-api.pro.coinbase.com-"));
+        assert_eq!(name, fixture_name(&request.redacted()));
+        assert_ne!(
+            name,
+            fixture_name(
+                &FixtureRequest {
+                    url: "https://api.pro.coinbase.com/products/ETH-USD/ticker",
+                    ..request
+                }
+                .redacted()
+            )
+        );
+    }
+}
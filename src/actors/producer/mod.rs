@@ -1,20 +1,59 @@
+use serde::Deserialize;
+
 mod apirequest;
-mod producertrait;
+mod auth;
+mod client;
+mod fixtures;
+mod pagination;
 mod publishdata;
+mod ratelimit;
+mod retry;
+mod translator;
 mod webproducer;
 
+pub use apirequest::{RequestBody, RequestLimits, ResponseInfo};
+pub use auth::{ApiKeyLocation, OAuth2Config, OAuth2Grant, OAuth2Provider, RequestAuth};
+pub use client::{configure_client, ClientConfig};
+pub(crate) use fixtures::REDACTED;
+pub use fixtures::{configure_fixtures, FixtureMode};
+pub use pagination::{NextPage, Pagination};
+pub(crate) use publishdata::publish_error;
+pub use ratelimit::{Rate, RateLimitConfig, RateLimiter};
+pub use retry::RetryPolicy;
+pub use translator::{
+    CsvColumns, CsvTranslator, JsonTranslator, RegexCaptures, RegexTranslator, ResponseFormat,
+    Translator, XmlFields, XmlTranslator,
+};
 pub use webproducer::WebProducer;
 
 /// Defines the type of action on Producer
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
 pub enum ProducerAction {
     PUBLISHDATA,
     STOREVARIABLE,
 }
 
+/// Use of `ETag` and `Last-Modified` to skip unchanged responses, for GET requests only
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConditionalRequest {
+    /// every request gets the full response
+    #[default]
+    Off,
+    /// send `If-None-Match` and `If-Modified-Since`, a 304 response publishes nothing
+    Skip,
+    /// as Skip, but a 304 response publishes the last data again stamped with the current time,
+    /// also when the response gave timestamps
+    Republish,
+}
+
 // Defines type of Web Request
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
 pub enum ApiRequestType {
     GET,
     POST,
+    PUT,
+    PATCH,
+    DELETE,
+    HEAD,
 }
@@ -0,0 +1,197 @@
+use crate::actors::producer::apirequest::with_query_param;
+use crate::error::{DataWatchError, DataWatchResult};
+use crate::jsonutility;
+use jmespatch::Expression;
+use surf::Url;
+
+/// Pages requested after the first, the results of every page are merged
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pagination {
+    pub next: NextPage,
+    /// pages requested at most, including the first
+    pub max_pages: u32,
+}
+
+/// How the request for the next page is made
+#[derive(Debug, Clone, PartialEq)]
+pub enum NextPage {
+    /// jmespath expression giving the url of the next page, relative to the current page
+    Link(String),
+    /// jmespath expression giving a cursor sent in query parameter `param`
+    Cursor { query: String, param: String },
+    /// query parameter `param` counting from `start` by `step`, for page numbers or offsets,
+    /// ends at a page without results
+    Offset {
+        param: String,
+        start: u64,
+        step: u64,
+    },
+}
+
+/// Compiled `Pagination` used by a producer to walk the pages of a response
+pub(crate) struct Pager {
+    pagination: Pagination,
+    query: Option<Expression<'static>>,
+}
+
+impl Pager {
+    pub fn new(pagination: &Pagination) -> DataWatchResult<Self> {
+        let query = match &pagination.next {
+            NextPage::Link(query) | NextPage::Cursor { query, .. } => {
+                Some(jmespatch::compile(query)?)
+            }
+            NextPage::Offset { .. } => None,
+        };
+        Ok(Pager {
+            pagination: pagination.clone(),
+            query,
+        })
+    }
+
+    pub fn max_pages(&self) -> u32 {
+        self.pagination.max_pages
+    }
+
+    /// url of the first page
+    pub fn first_url(&self, api_url: &str) -> DataWatchResult<String> {
+        match &self.pagination.next {
+            NextPage::Offset { param, start, .. } => {
+                with_query_param(api_url, param, &start.to_string())
+            }
+            _ => Ok(api_url.to_owned()),
+        }
+    }
+
+    /// url of the page after `page` (0 for the first), None when there are no more pages
+    ///
+    /// `page_url` and `response` are of the page just read, `empty` if it had no results
+    pub fn next_url(
+        &self,
+        api_url: &str,
+        page: u32,
+        page_url: &str,
+        response: &str,
+        empty: bool,
+    ) -> DataWatchResult<Option<String>> {
+        match (&self.pagination.next, &self.query) {
+            (NextPage::Offset { param, start, step }, _) => {
+                if empty {
+                    return Ok(None);
+                }
+                let value = start + step * u64::from(page + 1);
+                with_query_param(api_url, param, &value.to_string()).map(Some)
+            }
+            (NextPage::Link(_), Some(query)) => match search_text(query, response)? {
+                Some(link) => Url::parse(page_url)
+                    .and_then(|url| url.join(&link))
+                    .map(|url| Some(url.to_string()))
+                    .map_err(|e| DataWatchError::Request(format!("next page link: {}", e))),
+                None => Ok(None),
+            },
+            (NextPage::Cursor { param, .. }, Some(query)) => match search_text(query, response)? {
+                Some(cursor) => with_query_param(api_url, param, &cursor).map(Some),
+                None => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+}
+
+/// string or number found by `query`, None for null or an empty string
+fn search_text(query: &Expression<'static>, response: &str) -> DataWatchResult<Option<String>> {
+    let result = jsonutility::search_json(query, response)?;
+    if let Some(text) = result.as_string() {
+        Ok(Some(text.to_owned()).filter(|text| !text.is_empty()))
+    } else if let Some(number) = result.as_number() {
+        Ok(Some(number.to_string()))
+    } else if result.is_null() {
+        Ok(None)
+    } else {
+        Err(DataWatchError::Format(format!(
+            "next page is not a string or number: {}",
+            result
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pager(next: NextPage) -> Pager {
+        Pager::new(&Pagination { next, max_pages: 5 }).unwrap()
+    }
+
+    #[test]
+    fn next_link_relative_to_page() {
+        let pager = pager(NextPage::Link(String::from("links.next")));
+        let url = "https://example.com/v1/items?page=1";
+        assert_eq!(
+            pager
+                .next_url(
+                    url,
+                    0,
+                    url,
+                    r#"{"links": {"next": "/v1/items?page=2"}}"#,
+                    false
+                )
+                .unwrap(),
+            Some(String::from("https://example.com/v1/items?page=2"))
+        );
+        assert_eq!(
+            pager
+                .next_url(url, 1, url, r#"{"links": {"next": null}}"#, false)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn cursor_replaces_parameter() {
+        let pager = pager(NextPage::Cursor {
+            query: String::from("meta.cursor"),
+            param: String::from("after"),
+        });
+        let url = "https://example.com/items?limit=10&after=a1";
+        assert_eq!(
+            pager
+                .next_url(url, 0, url, r#"{"meta": {"cursor": "b 2"}}"#, false)
+                .unwrap(),
+            Some(String::from("https://example.com/items?limit=10&after=b+2"))
+        );
+        assert_eq!(
+            pager
+                .next_url(url, 0, url, r#"{"meta": {}}"#, false)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn offset_counts_until_empty_page() {
+        let pager = pager(NextPage::Offset {
+            param: String::from("offset"),
+            start: 0,
+            step: 100,
+        });
+        let url = "https://example.com/items";
+        assert_eq!(
+            pager.first_url(url).unwrap(),
+            "https://example.com/items?offset=0"
+        );
+        assert_eq!(
+            pager.next_url(url, 1, url, "[]", false).unwrap(),
+            Some(String::from("https://example.com/items?offset=200"))
+        );
+        assert_eq!(pager.next_url(url, 2, url, "[]", true).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_query_rejected() {
+        assert!(Pager::new(&Pagination {
+            next: NextPage::Link(String::from("[[")),
+            max_pages: 2,
+        })
+        .is_err());
+    }
+}
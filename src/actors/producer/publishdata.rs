@@ -1,27 +1,59 @@
-use crate::actors::messages::DataResponse;
-use std::collections::HashMap;
+use crate::actors::messages::{DataResponse, ProducerError};
+use crate::actors::producer::ResponseInfo;
+use crate::error::DataWatchError;
+use crate::DataSource;
 use std::time::{SystemTime, UNIX_EPOCH};
 use xactor::{Broker, Service};
 
 /// publish data in DataResponse format
-pub async fn publish_data(source_name: &str, data_response: HashMap<String, Vec<(String, f64)>>) {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+pub async fn publish_data(data_responses: Vec<DataResponse>) {
     let mut broker = Broker::from_registry().await.unwrap();
+    for data in data_responses {
+        broker.publish(data).unwrap();
+    }
+}
+
+/// convert parsed data into DataResponse format,
+/// values without a timestamp from the response are stamped with the current time
+pub fn to_data_responses(source_name: &str, data_response: DataSource) -> Vec<DataResponse> {
+    let timestamp = timestamp_now();
+    let mut out = Vec::new();
     for entry in data_response {
         let measure_name = entry.0;
         for data in entry.1 {
-            broker
-                .publish(DataResponse {
-                    source_name: source_name.to_owned(),
-                    measure_name: measure_name.to_owned(),
-                    measure_desc: data.0,
-                    measure_value: data.1,
-                    timestamp,
-                })
-                .unwrap();
+            out.push(DataResponse {
+                source_name: source_name.to_owned(),
+                measure_name: measure_name.to_owned(),
+                measure_desc: data.desc,
+                labels: data.labels,
+                measure_value: data.value,
+                timestamp: data.timestamp.unwrap_or(timestamp),
+            });
         }
     }
+    out
+}
+
+/// publish a failed request or parse in ProducerError format
+pub async fn publish_error(
+    source_name: &str,
+    error: DataWatchError,
+    response: Option<ResponseInfo>,
+) {
+    let mut broker = Broker::from_registry().await.unwrap();
+    broker
+        .publish(ProducerError {
+            source_name: source_name.to_owned(),
+            error,
+            response,
+            timestamp: timestamp_now(),
+        })
+        .unwrap();
+}
+
+pub(crate) fn timestamp_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
 }
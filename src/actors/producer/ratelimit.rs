@@ -0,0 +1,213 @@
+use crate::error::{DataWatchError, DataWatchResult};
+use async_std::sync::Mutex;
+use async_std::task;
+use log::debug;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use surf::Url;
+
+/// At most `requests` within `per`, refilled evenly over `per`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+/// Named request limit shared by every schedule using it
+///
+/// a schedule uses the limit it names, or else the limit listing the host of its url
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub name: String,
+    pub hosts: Vec<String>,
+    /// a request waits until every rate allows it
+    pub rates: Vec<Rate>,
+}
+
+/// Token buckets for a `RateLimitConfig`, requests over the limit wait their turn
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<Vec<Bucket>>,
+}
+
+struct Bucket {
+    capacity: f64,
+    /// time to refill one token
+    per_token: Duration,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: &Rate) -> Self {
+        let capacity = f64::from(rate.requests.max(1));
+        Bucket {
+            capacity,
+            per_token: rate.per.div_f64(capacity),
+            tokens: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() / self.per_token.as_secs_f64()).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// time until a token is available
+    fn wait(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            self.per_token.mul_f64(1.0 - self.tokens)
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        let buckets = config.rates.iter().map(Bucket::new).collect();
+        RateLimiter {
+            config,
+            buckets: Mutex::new(buckets),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// limiter named `name`, or else the first listing the host of `api_url`
+    pub fn select(
+        limiters: &HashMap<String, Arc<RateLimiter>>,
+        name: Option<&str>,
+        api_url: &str,
+    ) -> DataWatchResult<Option<Arc<RateLimiter>>> {
+        if let Some(name) = name {
+            return limiters
+                .get(name)
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| DataWatchError::Schedule(format!("unknown rate limit {}", name)));
+        }
+        let host = match Url::parse(api_url) {
+            Ok(url) => url.host_str().map(String::from),
+            Err(_) => None,
+        };
+        Ok(host.and_then(|host| {
+            limiters
+                .values()
+                .find(|limiter| limiter.config.hosts.contains(&host))
+                .cloned()
+        }))
+    }
+
+    /// wait until a request is allowed and count it,
+    /// waiting requests are let through one at a time in order
+    pub async fn acquire(&self) {
+        let mut buckets = self.buckets.lock().await;
+        let now = Instant::now();
+        let wait = buckets
+            .iter_mut()
+            .map(|bucket| bucket.wait(now))
+            .max()
+            .unwrap_or_default();
+        if wait > Duration::ZERO {
+            debug!(
+                "Rate limit {} delays request by {:?}",
+                self.config.name, wait
+            );
+            task::sleep(wait).await;
+        }
+        let now = Instant::now();
+        for bucket in buckets.iter_mut() {
+            bucket.refill(now);
+            bucket.tokens -= 1.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(name: &str, hosts: &[&str], rates: Vec<Rate>) -> Arc<RateLimiter> {
+        Arc::new(RateLimiter::new(RateLimitConfig {
+            name: name.to_owned(),
+            hosts: hosts.iter().map(|host| host.to_string()).collect(),
+            rates,
+        }))
+    }
+
+    #[async_std::test]
+    async fn requests_over_limit_are_delayed() {
+        // burst of 2, then one request every 100ms
+        let limiter = limiter(
+            "TEST",
+            &[],
+            vec![Rate {
+                requests: 2,
+                per: Duration::from_millis(200),
+            }],
+        );
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(400), "{:?}", elapsed);
+    }
+
+    #[async_std::test]
+    async fn slowest_rate_applies() {
+        let limiter = limiter(
+            "TEST",
+            &[],
+            vec![
+                Rate {
+                    requests: 10,
+                    per: Duration::from_secs(1),
+                },
+                Rate {
+                    requests: 1,
+                    per: Duration::from_millis(150),
+                },
+            ],
+        );
+        let start = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn select_by_name_or_host() {
+        let mut limiters = HashMap::new();
+        limiters.insert(
+            String::from("COINBASE"),
+            limiter("COINBASE", &["api.coinbase.com"], Vec::new()),
+        );
+        limiters.insert(String::from("TD"), limiter("TD", &[], Vec::new()));
+
+        let selected = |name, url| {
+            RateLimiter::select(&limiters, name, url)
+                .unwrap()
+                .map(|limiter| limiter.name().to_owned())
+        };
+        assert_eq!(
+            selected(None, "https://api.coinbase.com/v2/prices/BTC-USD/spot"),
+            Some(String::from("COINBASE"))
+        );
+        assert_eq!(
+            selected(Some("TD"), "https://api.coinbase.com/v2"),
+            Some(String::from("TD"))
+        );
+        assert_eq!(selected(None, "https://api.etherscan.io/api"), None);
+        assert!(RateLimiter::select(&limiters, Some("NONE"), "").is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::error::DataWatchError;
+use std::time::Duration;
+
+/// Retry policy for a failed api request
+///
+/// Delay before retry n is `base_delay * 2^(n-1)` capped at `max_delay`, then
+/// randomly moved up or down by the `jitter` fraction (0.0 to 1.0)
+///
+/// Transport errors and timeouts are always retried, http errors only if the status is in `retry_statuses`
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// total attempts including the first request
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: f64,
+    pub retry_statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.1,
+            retry_statuses: vec![408, 429, 500, 502, 503, 504],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// true if a request that failed on `attempt` (starting at 1) should be tried again
+    pub fn should_retry(&self, attempt: u32, error: &DataWatchError) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        match error {
+            DataWatchError::Request(_)
+            | DataWatchError::ConnectTimeout(_)
+            | DataWatchError::HeaderTimeout(_)
+            | DataWatchError::Timeout(_) => true,
+            DataWatchError::Status(status) => self.retry_statuses.contains(status),
+            _ => false,
+        }
+    }
+
+    /// delay before retrying a request that failed on `attempt` (starting at 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .checked_mul(1 << exponent)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        let jitter = self.jitter.clamp(0.0, 1.0) * (2.0 * fastrand::f64() - 1.0);
+        delay.mul_f64(1.0 + jitter).min(self.max_delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_delay_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+            jitter: 0.0,
+            retry_statuses: vec![503],
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(4), Duration::from_secs(5));
+        assert_eq!(policy.delay(40), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn jitter_stays_in_range() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_secs(10),
+            jitter: 0.5,
+            ..RetryPolicy::default()
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(1);
+            assert!(delay >= Duration::from_secs(5) && delay <= Duration::from_secs(15));
+        }
+    }
+
+    #[test]
+    fn retry_only_retryable_errors() {
+        let policy = RetryPolicy::default();
+        let timeout = DataWatchError::Request(String::from("connection reset"));
+        assert!(policy.should_retry(1, &timeout));
+        assert!(policy.should_retry(2, &DataWatchError::Status(503)));
+        assert!(!policy.should_retry(3, &DataWatchError::Status(503)));
+        assert!(!policy.should_retry(1, &DataWatchError::Status(404)));
+        assert!(!policy.should_retry(1, &DataWatchError::Json(String::new())));
+        assert!(policy.should_retry(1, &DataWatchError::Timeout(Duration::from_secs(1))));
+        assert!(!policy.should_retry(1, &DataWatchError::ResponseTooLarge(10)));
+    }
+}
@@ -0,0 +1,229 @@
+use super::{Fields, Translator};
+use crate::error::{DataWatchError, DataWatchResult};
+use crate::jsonutility::DroppedField;
+use crate::{DataPoint, DataSource, VarPairs};
+use csv::{ReaderBuilder, StringRecord, Trim};
+use serde::Deserialize;
+
+/// Columns of a csv table with a header row, each row is a measure
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CsvColumns {
+    /// column giving the measure_name of a row, rows with the same name are combined
+    pub name_column: String,
+    /// columns published with their header as measure_desc, every other column when empty
+    #[serde(default)]
+    pub value_columns: Vec<String>,
+    /// column giving the time of a row, see `jsonutility::parse_timestamp_text`
+    pub timestamp_column: Option<String>,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+/// Reads csv with `CsvColumns`, variables are the name column paired with the first value column
+pub struct CsvTranslator {
+    columns: CsvColumns,
+    delimiter: u8,
+    lenient: bool,
+}
+
+/// column positions found in the header row
+struct Layout {
+    name: usize,
+    values: Vec<(String, usize)>,
+    timestamp: Option<(String, usize)>,
+}
+
+impl CsvTranslator {
+    pub fn new(columns: CsvColumns, lenient: bool) -> DataWatchResult<Self> {
+        if !columns.delimiter.is_ascii() {
+            return Err(DataWatchError::Config(format!(
+                "csv delimiter {:?} is not a single byte",
+                columns.delimiter
+            )));
+        }
+        Ok(CsvTranslator {
+            delimiter: columns.delimiter as u8,
+            columns,
+            lenient,
+        })
+    }
+
+    /// rows of `response` with the column positions
+    fn read(&self, response: &str) -> DataWatchResult<(Layout, Vec<StringRecord>)> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .trim(Trim::All)
+            .flexible(true)
+            .from_reader(response.as_bytes());
+        let headers = reader.headers().map_err(csv_error)?.clone();
+        let position = |column: &str| {
+            headers
+                .iter()
+                .position(|header| header == column)
+                .ok_or_else(|| DataWatchError::Format(format!("csv has no column {}", column)))
+        };
+        let name = position(&self.columns.name_column)?;
+        let timestamp = match &self.columns.timestamp_column {
+            Some(column) => Some((column.clone(), position(column)?)),
+            None => None,
+        };
+        let values = if self.columns.value_columns.is_empty() {
+            headers
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| {
+                    *index != name && Some(*index) != timestamp.as_ref().map(|(_, index)| *index)
+                })
+                .map(|(index, header)| (header.to_owned(), index))
+                .collect()
+        } else {
+            self.columns
+                .value_columns
+                .iter()
+                .map(|column| Ok((column.clone(), position(column)?)))
+                .collect::<DataWatchResult<_>>()?
+        };
+        let rows = reader
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(csv_error)?;
+        Ok((
+            Layout {
+                name,
+                values,
+                timestamp,
+            },
+            rows,
+        ))
+    }
+}
+
+impl Translator for CsvTranslator {
+    fn data(&self, response: &str) -> DataWatchResult<(DataSource, Vec<DroppedField>)> {
+        let (layout, rows) = self.read(response)?;
+        let mut fields = Fields::new(self.lenient);
+        let mut out = DataSource::new();
+        for (row, record) in rows.iter().enumerate() {
+            let measure_name = match record.get(layout.name).filter(|name| !name.is_empty()) {
+                Some(name) => name,
+                None => {
+                    let row = format!("row {}", row + 1);
+                    fields.leave_out(&row, &self.columns.name_column, "is empty")?;
+                    continue;
+                }
+            };
+            let timestamp = match &layout.timestamp {
+                Some((column, index)) => {
+                    fields.timestamp(measure_name, column, record.get(*index))?
+                }
+                None => None,
+            };
+            let points = out.entry(measure_name.to_owned()).or_default();
+            for (column, index) in &layout.values {
+                if let Some(value) = fields.value(measure_name, column, record.get(*index))? {
+                    points.push(DataPoint::new(column.clone(), value, timestamp));
+                }
+            }
+        }
+        Ok((out, fields.dropped))
+    }
+
+    fn variables(&self, response: &str) -> DataWatchResult<VarPairs> {
+        let (layout, rows) = self.read(response)?;
+        let (column, index) = layout
+            .values
+            .first()
+            .ok_or_else(|| DataWatchError::Format(String::from("csv has no value column")))?;
+        rows.iter()
+            .enumerate()
+            .map(
+                |(row, record)| match (record.get(layout.name), record.get(*index)) {
+                    (Some(name), Some(value)) if !name.is_empty() => {
+                        Ok((name.to_owned(), value.to_owned()))
+                    }
+                    _ => Err(DataWatchError::Format(format!(
+                        "csv row {} has no {} or {}",
+                        row + 1,
+                        self.columns.name_column,
+                        column
+                    ))),
+                },
+            )
+            .collect()
+    }
+}
+
+fn csv_error(e: csv::Error) -> DataWatchError {
+    DataWatchError::Format(format!("csv: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::messages::MeasureValue;
+
+    const QUOTES: &str = "symbol,time,price,volume\n\
+                          SPY,2021-05-03T00:00:00Z,412.5,1200\n\
+                          QQQ,1620000000,,800\n";
+
+    fn translator(value_columns: &[&str], lenient: bool) -> CsvTranslator {
+        CsvTranslator::new(
+            CsvColumns {
+                name_column: String::from("symbol"),
+                value_columns: value_columns
+                    .iter()
+                    .map(|column| column.to_string())
+                    .collect(),
+                timestamp_column: Some(String::from("time")),
+                delimiter: ',',
+            },
+            lenient,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rows_become_measures() {
+        assert!(translator(&[], false).data(QUOTES).is_err());
+
+        let (data, dropped) = translator(&[], true).data(QUOTES).unwrap();
+        assert_eq!(
+            data["SPY"],
+            vec![
+                DataPoint::new(
+                    String::from("price"),
+                    MeasureValue::Float(412.5),
+                    Some(1620000000)
+                ),
+                DataPoint::new(
+                    String::from("volume"),
+                    MeasureValue::Integer(1200),
+                    Some(1620000000)
+                ),
+            ]
+        );
+        assert_eq!(data["QQQ"].len(), 1);
+        assert_eq!(dropped[0].to_string(), "QQQ.price is empty");
+
+        let (data, _) = translator(&["volume"], false).data(QUOTES).unwrap();
+        assert_eq!(data["QQQ"][0].value, MeasureValue::Integer(800));
+        assert!(translator(&["bid"], false).data(QUOTES).is_err());
+    }
+
+    #[test]
+    fn rows_become_variables() {
+        let pairs = translator(&["volume"], false).variables(QUOTES).unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                (String::from("SPY"), String::from("1200")),
+                (String::from("QQQ"), String::from("800")),
+            ]
+        );
+    }
+}
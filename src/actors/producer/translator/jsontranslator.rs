@@ -0,0 +1,37 @@
+use super::Translator;
+use crate::error::DataWatchResult;
+use crate::jsonutility::{self, DroppedField, Flatten};
+use crate::{DataSource, VarPairs};
+use jmespatch::Expression;
+
+/// Reads json with a jmespath expression, see `jsonutility::parse_json_data` for the format
+pub struct JsonTranslator {
+    expression: Expression<'static>,
+    flatten: Flatten,
+    lenient: bool,
+}
+
+impl JsonTranslator {
+    pub fn new(query: &str, flatten: Flatten, lenient: bool) -> DataWatchResult<Self> {
+        Ok(JsonTranslator {
+            expression: jmespatch::compile(query)?,
+            flatten,
+            lenient,
+        })
+    }
+}
+
+impl Translator for JsonTranslator {
+    fn data(&self, response: &str) -> DataWatchResult<(DataSource, Vec<DroppedField>)> {
+        if self.lenient {
+            jsonutility::parse_json_data_lenient(&self.expression, response, &self.flatten)
+        } else {
+            jsonutility::parse_json_data(&self.expression, response, &self.flatten)
+                .map(|data| (data, Vec::new()))
+        }
+    }
+
+    fn variables(&self, response: &str) -> DataWatchResult<VarPairs> {
+        jsonutility::parse_json_pair(&self.expression, response)
+    }
+}
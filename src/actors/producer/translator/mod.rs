@@ -0,0 +1,165 @@
+use crate::actors::messages::{MeasureValue, WebProducerSchedule};
+use crate::actors::producer::ProducerAction;
+use crate::error::DataWatchResult;
+use crate::jsonutility::{self, DroppedField};
+use crate::{DataSource, VarPairs};
+
+mod csvtranslator;
+mod jsontranslator;
+mod regextranslator;
+mod xmltranslator;
+
+pub use csvtranslator::{CsvColumns, CsvTranslator};
+pub use jsontranslator::JsonTranslator;
+pub use regextranslator::{RegexCaptures, RegexTranslator};
+pub use xmltranslator::{XmlFields, XmlTranslator};
+
+/// Reads a response body into data to publish or variables to store
+///
+/// every translator gives the same output, so the response action does not depend on the format
+pub trait Translator: Send + Sync {
+    /// measures in `response`, with the fields left out when lenient
+    fn data(&self, response: &str) -> DataWatchResult<(DataSource, Vec<DroppedField>)>;
+    /// name and value pairs in `response`
+    fn variables(&self, response: &str) -> DataWatchResult<VarPairs>;
+}
+
+/// Format of the response body, selects the translator of a schedule
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ResponseFormat {
+    /// json read with the jmespath expression `jmespatch_query`
+    #[default]
+    Json,
+    /// table with a header row
+    Csv(CsvColumns),
+    /// elements selected by path
+    Xml(XmlFields),
+    /// plain text read with a regular expression
+    Regex(RegexCaptures),
+}
+
+impl ResponseFormat {
+    /// translator for the format and parse settings of `schedule`
+    pub fn translator(schedule: &WebProducerSchedule) -> DataWatchResult<Box<dyn Translator>> {
+        let lenient = schedule.lenient;
+        let for_data = matches!(schedule.response_action, ProducerAction::PUBLISHDATA);
+        Ok(match &schedule.format {
+            ResponseFormat::Json => Box::new(JsonTranslator::new(
+                &schedule.jmespatch_query,
+                schedule.flatten.clone(),
+                lenient,
+            )?),
+            ResponseFormat::Csv(columns) => Box::new(CsvTranslator::new(columns.clone(), lenient)?),
+            ResponseFormat::Xml(fields) => Box::new(XmlTranslator::new(fields.clone(), lenient)),
+            ResponseFormat::Regex(captures) => {
+                Box::new(RegexTranslator::new(captures.clone(), lenient, for_data)?)
+            }
+        })
+    }
+}
+
+/// number, `true` or `false`, or else the text
+fn text_value(text: &str) -> MeasureValue {
+    match text.trim() {
+        "true" => MeasureValue::Bool(true),
+        "false" => MeasureValue::Bool(false),
+        trimmed => {
+            jsonutility::numeric_text(trimmed).unwrap_or_else(|| MeasureValue::from(trimmed))
+        }
+    }
+}
+
+/// Fields read by the text formats, a missing or empty one fails the translation
+/// or is left out and reported when lenient
+struct Fields {
+    lenient: bool,
+    dropped: Vec<DroppedField>,
+}
+
+impl Fields {
+    fn new(lenient: bool) -> Self {
+        Fields {
+            lenient,
+            dropped: Vec::new(),
+        }
+    }
+
+    /// value of `field` in measure `measure_name`, None when left out
+    fn value(
+        &mut self,
+        measure_name: &str,
+        field: &str,
+        text: Option<&str>,
+    ) -> DataWatchResult<Option<MeasureValue>> {
+        let reason = match text.map(str::trim) {
+            Some("") => "is empty",
+            Some(text) => return Ok(Some(text_value(text))),
+            None => "is missing",
+        };
+        self.leave_out(measure_name, field, reason)?;
+        Ok(None)
+    }
+
+    /// timestamp in `field`, None when the field is absent or left out
+    fn timestamp(
+        &mut self,
+        measure_name: &str,
+        field: &str,
+        text: Option<&str>,
+    ) -> DataWatchResult<Option<u64>> {
+        match text.map(str::trim).filter(|text| !text.is_empty()) {
+            Some(text) => match jsonutility::parse_timestamp_text(text) {
+                Some(timestamp) => Ok(Some(timestamp)),
+                None => {
+                    let reason = format!("is not epoch seconds, milliseconds or RFC3339: {}", text);
+                    self.leave_out(measure_name, field, &reason)?;
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn leave_out(&mut self, measure_name: &str, field: &str, reason: &str) -> DataWatchResult<()> {
+        let dropped = if self.lenient {
+            Some(&mut self.dropped)
+        } else {
+            None
+        };
+        jsonutility::leave_out(
+            dropped,
+            DroppedField {
+                measure_name: measure_name.to_owned(),
+                field: field.to_owned(),
+                reason: reason.to_owned(),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_values_typed() {
+        assert_eq!(text_value(" 42 "), MeasureValue::Integer(42));
+        assert_eq!(text_value("4.5e1"), MeasureValue::Float(45.0));
+        assert_eq!(text_value("false"), MeasureValue::Bool(false));
+        assert_eq!(text_value("closed"), MeasureValue::from("closed"));
+    }
+
+    #[test]
+    fn missing_fields_fail_unless_lenient() {
+        let mut strict = Fields::new(false);
+        assert!(strict.value("A", "price", None).is_err());
+        assert!(strict.timestamp("A", "time", Some("later")).is_err());
+        assert_eq!(strict.timestamp("A", "time", None).unwrap(), None);
+
+        let mut lenient = Fields::new(true);
+        assert_eq!(lenient.value("A", "price", Some(" ")).unwrap(), None);
+        assert_eq!(lenient.timestamp("A", "time", Some("later")).unwrap(), None);
+        assert_eq!(lenient.dropped.len(), 2);
+        assert_eq!(lenient.dropped[0].to_string(), "A.price is empty");
+    }
+}
@@ -0,0 +1,173 @@
+use super::{Fields, Translator};
+use crate::error::{DataWatchError, DataWatchResult};
+use crate::jsonutility::DroppedField;
+use crate::{DataPoint, DataSource, VarPairs};
+use regex::Regex;
+use serde::Deserialize;
+
+/// Regular expression over a text response, each match is a measure
+///
+/// every named group is a measure_desc, except `measure_name` naming the measure
+/// and `timestamp` giving its time, see `jsonutility::parse_timestamp_text`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegexCaptures {
+    pub pattern: String,
+    /// name of every measure when the pattern has no `measure_name` group
+    pub measure_name: Option<String>,
+}
+
+/// Reads text with `RegexCaptures`, variables are the named groups of every match
+pub struct RegexTranslator {
+    regex: Regex,
+    measure_name: Option<String>,
+    values: Vec<String>,
+    lenient: bool,
+}
+
+impl RegexTranslator {
+    /// `for_data` requires a measure_name group or `measure_name`, variables need neither
+    pub fn new(captures: RegexCaptures, lenient: bool, for_data: bool) -> DataWatchResult<Self> {
+        let regex = Regex::new(&captures.pattern)
+            .map_err(|e| DataWatchError::Config(format!("regex: {}", e)))?;
+        let names: Vec<&str> = regex.capture_names().flatten().collect();
+        let name_group = names.contains(&"measure_name");
+        let values: Vec<String> = names
+            .into_iter()
+            .filter(|name| *name != "measure_name" && *name != "timestamp")
+            .map(String::from)
+            .collect();
+        if values.is_empty() {
+            return Err(DataWatchError::Config(String::from(
+                "regex needs a named group for a value",
+            )));
+        }
+        if for_data && !name_group && captures.measure_name.is_none() {
+            return Err(DataWatchError::Config(String::from(
+                "regex needs a measure_name group or measure_name",
+            )));
+        }
+        Ok(RegexTranslator {
+            regex,
+            measure_name: captures.measure_name.filter(|_| !name_group),
+            values,
+            lenient,
+        })
+    }
+}
+
+impl Translator for RegexTranslator {
+    fn data(&self, response: &str) -> DataWatchResult<(DataSource, Vec<DroppedField>)> {
+        let mut fields = Fields::new(self.lenient);
+        let mut out = DataSource::new();
+        for (index, captures) in self.regex.captures_iter(response).enumerate() {
+            let group = |name: &str| captures.name(name).map(|found| found.as_str());
+            let measure_name = match (&self.measure_name, group("measure_name")) {
+                (Some(name), _) => name.as_str(),
+                (None, Some(name)) if !name.trim().is_empty() => name.trim(),
+                (None, _) => {
+                    let found = format!("match {}", index + 1);
+                    fields.leave_out(&found, "measure_name", "is missing")?;
+                    continue;
+                }
+            };
+            let timestamp = fields.timestamp(measure_name, "timestamp", group("timestamp"))?;
+            let points = out.entry(measure_name.to_owned()).or_default();
+            for desc in &self.values {
+                if let Some(value) = fields.value(measure_name, desc, group(desc))? {
+                    points.push(DataPoint::new(desc.clone(), value, timestamp));
+                }
+            }
+        }
+        Ok((out, fields.dropped))
+    }
+
+    fn variables(&self, response: &str) -> DataWatchResult<VarPairs> {
+        let mut out = Vec::new();
+        for captures in self.regex.captures_iter(response) {
+            for name in &self.values {
+                let value = captures.name(name).ok_or_else(|| {
+                    DataWatchError::Format(format!("regex group {} did not match", name))
+                })?;
+                out.push((name.clone(), value.as_str().to_owned()));
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::messages::MeasureValue;
+
+    const STATUS: &str = "SPY last=412.5 open=true at 1620000000\n\
+                          QQQ last= open=false at 1620000000\n";
+
+    fn translator(pattern: &str, lenient: bool) -> DataWatchResult<RegexTranslator> {
+        RegexTranslator::new(
+            RegexCaptures {
+                pattern: pattern.to_owned(),
+                measure_name: None,
+            },
+            lenient,
+            true,
+        )
+    }
+
+    #[test]
+    fn matches_become_measures() {
+        let pattern = r"(?m)^(?P<measure_name>\w+) last=(?P<last>\S*) open=(?P<open>\w+) at (?P<timestamp>\d+)$";
+        assert!(translator(pattern, false).unwrap().data(STATUS).is_err());
+
+        let (data, dropped) = translator(pattern, true).unwrap().data(STATUS).unwrap();
+        assert_eq!(
+            data["SPY"],
+            vec![
+                DataPoint::new(
+                    String::from("last"),
+                    MeasureValue::Float(412.5),
+                    Some(1620000000)
+                ),
+                DataPoint::new(
+                    String::from("open"),
+                    MeasureValue::Bool(true),
+                    Some(1620000000)
+                ),
+            ]
+        );
+        assert_eq!(data["QQQ"].len(), 1);
+        assert_eq!(dropped[0].to_string(), "QQQ.last is empty");
+    }
+
+    #[test]
+    fn named_groups_become_variables() {
+        let captures = RegexCaptures {
+            pattern: String::from(r"TOKEN=(?P<TOKEN>\w+)"),
+            measure_name: None,
+        };
+        let translator = RegexTranslator::new(captures.clone(), false, false).unwrap();
+        assert_eq!(
+            translator.variables("TOKEN=abc").unwrap(),
+            vec![(String::from("TOKEN"), String::from("abc"))]
+        );
+        assert!(RegexTranslator::new(captures, false, true).is_err());
+    }
+
+    #[test]
+    fn pattern_needs_value_group() {
+        assert!(translator(r"(?P<measure_name>\w+)", false).is_err());
+        assert!(translator(r"(?P<measure_name>\w+", false).is_err());
+        let fixed = RegexTranslator::new(
+            RegexCaptures {
+                pattern: String::from(r"price (?P<price>\S+)"),
+                measure_name: Some(String::from("SPY")),
+            },
+            false,
+            true,
+        )
+        .unwrap();
+        let (data, _) = fixed.data("price 412.5").unwrap();
+        assert_eq!(data["SPY"][0].value, MeasureValue::Float(412.5));
+    }
+}
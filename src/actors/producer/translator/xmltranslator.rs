@@ -0,0 +1,223 @@
+use super::{Fields, Translator};
+use crate::error::{DataWatchError, DataWatchResult};
+use crate::jsonutility::DroppedField;
+use crate::{DataPoint, DataSource, VarPairs};
+use roxmltree::{Document, Node};
+use serde::Deserialize;
+
+/// Elements of an xml document, each element selected by `measures` is a measure
+///
+/// paths are element names separated by `/`, `*` matches any element, and a field can end
+/// in `@name` for an attribute, such as `quotes/quote` with the fields `@symbol` or `bid/@size`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct XmlFields {
+    /// path from the document to the measure elements
+    pub measures: String,
+    /// field giving the measure_name, elements with the same name are combined
+    pub name: String,
+    /// fields published as measure_desc without `@`, every child element with text when empty
+    #[serde(default)]
+    pub values: Vec<String>,
+    /// field giving the time of a measure, see `jsonutility::parse_timestamp_text`
+    pub timestamp: Option<String>,
+}
+
+/// Reads xml with `XmlFields`, variables are the name field paired with the first value field
+pub struct XmlTranslator {
+    fields: XmlFields,
+    lenient: bool,
+}
+
+impl XmlTranslator {
+    pub fn new(fields: XmlFields, lenient: bool) -> Self {
+        XmlTranslator { fields, lenient }
+    }
+}
+
+impl Translator for XmlTranslator {
+    fn data(&self, response: &str) -> DataWatchResult<(DataSource, Vec<DroppedField>)> {
+        let document = parse(response)?;
+        let mut fields = Fields::new(self.lenient);
+        let mut out = DataSource::new();
+        for (index, element) in select(document.root(), &self.fields.measures)
+            .into_iter()
+            .enumerate()
+        {
+            let measure_name = match field_text(element, &self.fields.name) {
+                Some(name) if !name.trim().is_empty() => name.trim(),
+                _ => {
+                    let element = format!("element {}", index + 1);
+                    fields.leave_out(&element, &self.fields.name, "is missing")?;
+                    continue;
+                }
+            };
+            let timestamp = match &self.fields.timestamp {
+                Some(field) => fields.timestamp(measure_name, field, field_text(element, field))?,
+                None => None,
+            };
+            let values: Vec<(String, Option<&str>)> = if self.fields.values.is_empty() {
+                element
+                    .children()
+                    .filter(|child| {
+                        child.is_element() && !child.children().any(|inner| inner.is_element())
+                    })
+                    .map(|child| (child.tag_name().name().to_owned(), child.text()))
+                    .filter(|(_, text)| text.is_some_and(|text| !text.trim().is_empty()))
+                    .collect()
+            } else {
+                self.fields
+                    .values
+                    .iter()
+                    .map(|field| (field.replace('@', ""), field_text(element, field)))
+                    .collect()
+            };
+            let points = out.entry(measure_name.to_owned()).or_default();
+            for (desc, text) in values {
+                if let Some(value) = fields.value(measure_name, &desc, text)? {
+                    points.push(DataPoint::new(desc, value, timestamp));
+                }
+            }
+        }
+        Ok((out, fields.dropped))
+    }
+
+    fn variables(&self, response: &str) -> DataWatchResult<VarPairs> {
+        let document = parse(response)?;
+        let field = self
+            .fields
+            .values
+            .first()
+            .ok_or_else(|| DataWatchError::Format(String::from("xml has no value field")))?;
+        select(document.root(), &self.fields.measures)
+            .into_iter()
+            .map(|element| {
+                match (
+                    field_text(element, &self.fields.name),
+                    field_text(element, field),
+                ) {
+                    (Some(name), Some(value)) if !name.trim().is_empty() => {
+                        Ok((name.trim().to_owned(), value.trim().to_owned()))
+                    }
+                    _ => Err(DataWatchError::Format(format!(
+                        "xml element {} has no {} or {}",
+                        element.tag_name().name(),
+                        self.fields.name,
+                        field
+                    ))),
+                }
+            })
+            .collect()
+    }
+}
+
+fn parse(response: &str) -> DataWatchResult<Document<'_>> {
+    Document::parse(response).map_err(|e| DataWatchError::Format(format!("xml: {}", e)))
+}
+
+/// elements reached from `from` by the element names in `path`
+fn select<'a, 'input>(from: Node<'a, 'input>, path: &str) -> Vec<Node<'a, 'input>> {
+    let mut nodes = vec![from];
+    for step in path.split('/').filter(|step| !step.is_empty()) {
+        nodes = nodes
+            .iter()
+            .flat_map(|node| node.children())
+            .filter(|child| child.is_element() && (step == "*" || child.tag_name().name() == step))
+            .collect();
+    }
+    nodes
+}
+
+/// text of the first element at `field` below `element`, or its attribute after `@`
+fn field_text<'a>(element: Node<'a, '_>, field: &str) -> Option<&'a str> {
+    let (path, attribute) = match field.rfind('@') {
+        Some(at) => (&field[..at], Some(&field[at + 1..])),
+        None => (field, None),
+    };
+    let node = select(element, path).into_iter().next()?;
+    match attribute {
+        Some(name) => node.attribute(name),
+        None => Some(node.text().unwrap_or("")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::messages::MeasureValue;
+
+    const QUOTES: &str = r#"<?xml version="1.0"?>
+        <quotes>
+            <quote symbol="SPY" time="1620000000">
+                <price>412.5</price>
+                <bid size="300">412.4</bid>
+            </quote>
+            <quote symbol="QQQ">
+                <price></price>
+                <bid size="100">330.1</bid>
+            </quote>
+        </quotes>"#;
+
+    fn translator(values: &[&str], lenient: bool) -> XmlTranslator {
+        XmlTranslator::new(
+            XmlFields {
+                measures: String::from("quotes/quote"),
+                name: String::from("@symbol"),
+                values: values.iter().map(|value| value.to_string()).collect(),
+                timestamp: Some(String::from("@time")),
+            },
+            lenient,
+        )
+    }
+
+    #[test]
+    fn elements_become_measures() {
+        let (data, dropped) = translator(&["price", "bid/@size"], true)
+            .data(QUOTES)
+            .unwrap();
+        assert_eq!(
+            data["SPY"],
+            vec![
+                DataPoint::new(
+                    String::from("price"),
+                    MeasureValue::Float(412.5),
+                    Some(1620000000)
+                ),
+                DataPoint::new(
+                    String::from("bid/size"),
+                    MeasureValue::Integer(300),
+                    Some(1620000000)
+                ),
+            ]
+        );
+        assert_eq!(data["QQQ"].len(), 1);
+        assert_eq!(dropped[0].to_string(), "QQQ.price is empty");
+        assert!(translator(&["price"], false).data(QUOTES).is_err());
+
+        let (data, _) = translator(&[], false).data(QUOTES).unwrap();
+        assert_eq!(data["SPY"].len(), 2);
+        assert_eq!(
+            data["QQQ"],
+            vec![DataPoint::new(
+                String::from("bid"),
+                MeasureValue::Float(330.1),
+                None
+            )]
+        );
+        assert!(translator(&[], false)
+            .data("<quotes><quote></quotes>")
+            .is_err());
+    }
+
+    #[test]
+    fn elements_become_variables() {
+        let pairs = translator(&["bid"], false).variables(QUOTES).unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                (String::from("SPY"), String::from("412.4")),
+                (String::from("QQQ"), String::from("330.1")),
+            ]
+        );
+    }
+}
@@ -1,26 +1,65 @@
-use crate::actors::messages::{Refresh, Run, WebProducerSchedule};
-use crate::actors::producer::{apirequest::request_api, publishdata::publish_data, ProducerAction};
-use crate::{jsonutility, varstore, DataSource, VarPairs};
+use crate::actors::messages::{
+    DataResponse, ProducerStats, Refresh, Run, RunComplete, RunNow, Stats, StatsUpdate,
+    WebProducerSchedule,
+};
+use crate::actors::producer::{
+    apirequest::request_api,
+    pagination::Pager,
+    publishdata::{publish_data, publish_error, to_data_responses},
+    ApiRequestType, ConditionalRequest, OAuth2Provider, ProducerAction, RateLimiter, RequestAuth,
+    ResponseFormat, ResponseInfo, Translator,
+};
+use crate::calendar::Calendar;
+use crate::error::{DataWatchError, DataWatchResult};
+use crate::schedule::FireSchedule;
+use crate::{varstore, DataSource, VarPairs};
+use async_std::task;
 use async_trait::async_trait;
-use chrono::Utc;
-use cron::Schedule;
-use jmespatch::Expression;
-use log::{debug, info};
-use std::str::FromStr;
+use chrono::{DateTime, Utc};
+use http_types::Mime;
+use log::{debug, error, info, warn};
+use std::sync::Arc;
 use std::time::Duration;
 use xactor::*;
 
 /// Creates a web API request that runs on a schedule and publishes data
-/// uses jmespath expression to parse out relevant data
-/// uses cron expression to determining timing of stream
-
-// IDEA: if things get slow, can we share a client amongst the actors or send the request to a separate broker who handles requests
-// IDEA: or store the client state.  currently the request is rebuilt each time
-
+/// uses the translator of the response format to parse out relevant data
+/// uses cron expression or fixed interval to determining timing of stream
+///
+/// a failed request is retried according to the schedule retry policy,
+/// every attempt waits for the rate limit shared with other producers
+/// a request refused with 401 gets a new OAuth2 access token and is sent once more
+/// with conditional requests on, a GET request sends the validators of the last parsed response
+/// a request that still fails or a failed parse is logged and published as `ProducerError`
+/// with the status, headers and latency of the response, the schedule keeps running
 pub struct WebProducer {
-    translation: Expression<'static>,
-    schedule: Schedule,
+    translator: Box<dyn Translator>,
+    schedule: FireSchedule,
     request_description: WebProducerSchedule,
+    next_run: Option<DateTime<Utc>>,
+    retries: u64,
+    reporter: Option<Reporter>,
+    auth_provider: Option<Arc<OAuth2Provider>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    last_response: Option<ResponseInfo>,
+    cached: CachedResponse,
+    pager: Option<Arc<Pager>>,
+}
+
+/// Validators and data of the last parsed response, kept for conditional requests
+#[derive(Default)]
+struct CachedResponse {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// only kept for `ConditionalRequest::Republish`
+    data: DataSource,
+}
+
+/// Scheduler senders a producer reports to under its schedule id
+struct Reporter {
+    id: u64,
+    stats: Sender<StatsUpdate>,
+    runs: Sender<RunComplete>,
 }
 
 #[async_trait]
@@ -43,9 +82,23 @@ impl Handler<Refresh> for WebProducer {
             "<Refresh> received for {}:",
             &self.request_description.source_name
         );
-        let next = self.schedule.upcoming(Utc).next().unwrap();
-        let diff = next - Utc::now() + chrono::Duration::milliseconds(100);
-        ctx.send_later(Run {}, diff.to_std().unwrap());
+        self.next_run = match self.next_run {
+            None => self.schedule.first_after(Utc::now()),
+            Some(_) => self.schedule.next_after(Utc::now()),
+        };
+        match self.next_run {
+            Some(next) => {
+                let diff = next - Utc::now() + chrono::Duration::milliseconds(100);
+                ctx.send_later(Run {}, diff.to_std().unwrap_or_default());
+            }
+            None => {
+                info!(
+                    "No upcoming runs left for {}",
+                    &self.request_description.source_name
+                );
+            }
+        }
+        self.report_stats();
     }
 }
 
@@ -61,68 +114,841 @@ impl Handler<Run> for WebProducer {
     }
 }
 
+#[async_trait]
+impl Handler<RunNow> for WebProducer {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: RunNow) {
+        info!(
+            "<RunNow> received for {}:",
+            &self.request_description.source_name
+        );
+        self.run_request().await;
+    }
+}
+
+#[async_trait]
+impl Handler<Stats> for WebProducer {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: Stats) -> ProducerStats {
+        self.stats()
+    }
+}
+
 impl WebProducer {
-    pub fn new(request_description: WebProducerSchedule) -> Self {
-        let translation = jmespatch::compile(request_description.jmespatch_query.as_ref()).unwrap();
-        let schedule = Schedule::from_str(request_description.cron.as_str()).unwrap();
-        WebProducer {
-            translation,
+    pub fn new(request_description: WebProducerSchedule) -> DataWatchResult<Self> {
+        let translator = ResponseFormat::translator(&request_description)?;
+        if let Some(body) = &request_description.body {
+            body.content_type()?;
+        }
+        let mut schedule = FireSchedule::new(&request_description.schedule)?;
+        if let Some(timezone) = &request_description.timezone {
+            schedule = schedule.with_timezone(timezone)?;
+        }
+        let pager = match &request_description.pagination {
+            Some(pagination) => Some(Arc::new(Pager::new(pagination)?)),
+            None => None,
+        };
+        Ok(WebProducer {
+            translator,
             schedule,
             request_description,
+            next_run: None,
+            retries: 0,
+            reporter: None,
+            auth_provider: None,
+            rate_limiter: None,
+            last_response: None,
+            cached: CachedResponse::default(),
+            pager,
+        })
+    }
+
+    /// read responses with `translator` in place of the one for the response format
+    pub fn with_translator(mut self, translator: Box<dyn Translator>) -> Self {
+        self.translator = translator;
+        self
+    }
+
+    /// skip fire times on dates excluded by calendar
+    pub fn with_calendar(mut self, calendar: Arc<Calendar>) -> Self {
+        self.schedule = self.schedule.with_calendar(calendar);
+        self
+    }
+
+    /// provider of the access token for `RequestAuth::OAuth2`
+    pub fn with_auth_provider(mut self, provider: Arc<OAuth2Provider>) -> Self {
+        self.auth_provider = Some(provider);
+        self
+    }
+
+    /// delay every attempt until the shared rate limit allows it
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// report the result of every request and every change of stats to the Scheduler
+    /// under schedule `id`
+    pub fn with_reporter(
+        mut self,
+        id: u64,
+        stats: Sender<StatsUpdate>,
+        runs: Sender<RunComplete>,
+    ) -> Self {
+        self.reporter = Some(Reporter { id, stats, runs });
+        self
+    }
+
+    fn stats(&self) -> ProducerStats {
+        ProducerStats {
+            next_run: self.next_run,
+            retries: self.retries,
+            last_response: self.last_response.clone(),
         }
     }
 
-    async fn run_request(&mut self) {
-        let response = self.get_request().await;
-        self.response_action(&response).await;
+    fn report_stats(&self) {
+        if let Some(reporter) = &self.reporter {
+            let update = StatsUpdate {
+                id: reporter.id,
+                stats: self.stats(),
+            };
+            reporter.stats.send(update).ok();
+        }
     }
 
-    /// Builds and runs request
-    async fn get_request(&mut self) -> String {
-        // swap variables in api_url, body, header for [[ ]]
-        let api_url = varstore::swap_variable(
-            &self.request_description.storage_var,
-            &self.request_description.api_url,
-            true,
-        );
-        let body = match self.request_description.body.clone() {
-            Some(b) => varstore::swap_variable(&self.request_description.storage_var, &b, true),
-            None => String::from(""),
+    /// Runs request once and applies the response action without publishing
+    /// variables are stored, data is returned in DataResponse format
+    /// a 304 response is not parsed, see `ConditionalRequest`
+    /// with pagination the results of every page are merged
+    pub async fn run_once(&mut self) -> DataWatchResult<Vec<DataResponse>> {
+        let PreparedRequest {
+            api_url,
+            body,
+            headers,
+        } = self.build_request()?;
+        let pager = self.pager.clone();
+        let mut page_url = match &pager {
+            Some(pager) => pager.first_url(&api_url)?,
+            None => api_url.clone(),
         };
-        let header = match &self.request_description.header {
-            Some((key, value)) => {
-                let new_value =
-                    varstore::swap_variable(&self.request_description.storage_var, &value, false);
-                Some((key.as_str(), new_value))
+
+        let mut first_headers = headers.clone();
+        if self.is_conditional() {
+            if let Some(etag) = &self.cached.etag {
+                first_headers.push((String::from("If-None-Match"), etag.clone()));
             }
-            None => None,
+            if let Some(last_modified) = &self.cached.last_modified {
+                first_headers.push((String::from("If-Modified-Since"), last_modified.clone()));
+            }
+        }
+        let mut response = match self.send(&page_url, &body, &first_headers).await? {
+            Some(response) => response,
+            None => return Ok(self.not_modified()),
+        };
+        let first_response = self.last_response.clone();
+        let mut translated = self.translate(&response)?;
+
+        if let Some(pager) = pager {
+            let mut empty = translated.is_empty();
+            for page in 0..pager.max_pages().saturating_sub(1) {
+                page_url = match pager.next_url(&api_url, page, &page_url, &response, empty)? {
+                    Some(next_url) => next_url,
+                    None => break,
+                };
+                response = match self.send(&page_url, &body, &headers).await? {
+                    Some(response) => response,
+                    None => break,
+                };
+                let page_translated = self.translate(&response)?;
+                empty = page_translated.is_empty();
+                translated.merge(page_translated);
+            }
+        }
+
+        self.cache_response(first_response.as_ref(), &translated);
+        Ok(self.response_action(translated))
+    }
+
+    fn is_conditional(&self) -> bool {
+        self.request_description.conditional != ConditionalRequest::Off
+            && matches!(self.request_description.request_type, ApiRequestType::GET)
+    }
+
+    /// keep validators only once the response is parsed, so a failed parse is requested again
+    fn cache_response(&mut self, response: Option<&ResponseInfo>, translated: &Translated) {
+        if !self.is_conditional() {
+            return;
+        }
+        let header = |name| {
+            response
+                .and_then(|info| info.header(name))
+                .map(String::from)
+        };
+        let etag = header("etag");
+        let last_modified = header("last-modified");
+        let data = match (self.request_description.conditional, translated) {
+            (ConditionalRequest::Republish, Translated::Data(data)) => data.clone(),
+            _ => DataSource::new(),
         };
+        self.cached = CachedResponse {
+            etag,
+            last_modified,
+            data,
+        };
+    }
+
+    /// data for a 304 response stamped with the current time
+    fn not_modified(&self) -> Vec<DataResponse> {
+        info!(
+            "Not modified since last request for {}",
+            &self.request_description.source_name
+        );
+        let mut data = self.cached.data.clone();
+        for point in data.values_mut().flatten() {
+            point.timestamp = None;
+        }
+        to_data_responses(&self.request_description.source_name, data)
+    }
 
-        request_api(
-            &self.request_description.request_type,
-            &api_url,
-            &body,
-            header,
-        )
-        .await
+    async fn run_request(&mut self) {
+        let error = match self.run_once().await {
+            Ok(data_responses) => {
+                publish_data(data_responses).await;
+                None
+            }
+            Err(e) => {
+                error!(
+                    "Request failed for {}: {}",
+                    &self.request_description.source_name, e
+                );
+                publish_error(
+                    &self.request_description.source_name,
+                    e.clone(),
+                    self.last_response.clone(),
+                )
+                .await;
+                Some(e)
+            }
+        };
+        self.report_stats();
+        if let Some(reporter) = &self.reporter {
+            let id = reporter.id;
+            reporter.runs.send(RunComplete { id, error }).ok();
+        }
     }
 
-    fn translate_for_publish_data(&self, response: &str) -> DataSource {
-        jsonutility::parse_json_data(&self.translation, &response)
+    /// request with variables swapped in
+    fn build_request(&self) -> DataWatchResult<PreparedRequest> {
+        // swap variables in api_url, body, headers for [[ ]]
+        let storage_var = &self.request_description.storage_var;
+        let api_url = varstore::swap_variable(storage_var, &self.request_description.api_url, true);
+        let body = match &self.request_description.body {
+            Some(body) => Some((body.content(storage_var), body.content_type()?)),
+            None => None,
+        };
+        let headers: Vec<(String, String)> = self
+            .request_description
+            .headers
+            .iter()
+            .map(|(key, value)| {
+                (
+                    key.clone(),
+                    varstore::swap_variable(storage_var, value, false),
+                )
+            })
+            .collect();
+
+        if let Some(RequestAuth::OAuth2(name)) = &self.request_description.auth {
+            if self.auth_provider.is_none() {
+                return Err(DataWatchError::Auth(format!(
+                    "provider {} is not available",
+                    name
+                )));
+            }
+        }
+        Ok(PreparedRequest {
+            api_url,
+            body,
+            headers,
+        })
     }
 
-    fn translate_for_variable_store(&self, response: &str) -> VarPairs {
-        jsonutility::parse_json_pair(&self.translation, &response)
+    /// sends request with retries, None for a 304 response
+    async fn send(
+        &mut self,
+        api_url: &str,
+        body: &Option<(String, Mime)>,
+        headers: &[(String, String)],
+    ) -> DataWatchResult<Option<String>> {
+        // static auth is added to every page, next page links do not carry api keys
+        let mut api_url = api_url.to_owned();
+        let mut request_headers = headers.to_vec();
+        if let Some(auth) = &self.request_description.auth {
+            auth.apply(
+                &self.request_description.storage_var,
+                &mut api_url,
+                &mut request_headers,
+            )?;
+        }
+
+        let secrets = match &self.request_description.auth {
+            Some(auth) => auth.secret_names(),
+            None => Vec::new(),
+        };
+        let mut attempt = 1;
+        let mut renewed_token = false;
+        loop {
+            let mut headers = request_headers.clone();
+            if let Some(provider) = &self.auth_provider {
+                let token = provider.token().await?;
+                headers.push((String::from("Authorization"), format!("Bearer {}", token)));
+            }
+            if let Some(limiter) = &self.rate_limiter {
+                limiter.acquire().await;
+            }
+            let response = match request_api(
+                &self.request_description.request_type,
+                &api_url,
+                body.clone(),
+                &headers,
+                &self.request_description.limits,
+                &secrets,
+            )
+            .await
+            {
+                Ok(response) if response.info.status == 304 => {
+                    self.last_response = Some(response.info);
+                    return Ok(None);
+                }
+                Ok(response) => {
+                    self.last_response = Some(response.info.clone());
+                    response.into_body().map(Some)
+                }
+                Err(e) => {
+                    self.last_response = None;
+                    Err(e)
+                }
+            };
+            match (response, &self.request_description.retry) {
+                (Err(DataWatchError::Status(401)), _)
+                    if !renewed_token && self.auth_provider.is_some() =>
+                {
+                    warn!(
+                        "Access token refused for {}, renewing",
+                        &self.request_description.source_name
+                    );
+                    if let Some(provider) = &self.auth_provider {
+                        provider.invalidate().await;
+                    }
+                    renewed_token = true;
+                }
+                (Err(e), Some(retry)) if retry.should_retry(attempt, &e) => {
+                    let delay = retry.delay(attempt);
+                    self.retries += 1;
+                    warn!(
+                        "Retry {}/{} for {} in {:?} after: {}",
+                        attempt,
+                        retry.max_attempts - 1,
+                        &self.request_description.source_name,
+                        delay,
+                        e
+                    );
+                    task::sleep(delay).await;
+                    attempt += 1;
+                }
+                (response, _) => return response,
+            }
+        }
     }
 
-    async fn response_action(&self, response: &String) {
+    fn translate(&self, response: &str) -> DataWatchResult<Translated> {
         match &self.request_description.response_action {
             ProducerAction::PUBLISHDATA => {
-                publish_data(&self.request_description.source_name, self.translate_for_publish_data(response)).await;
+                let (mut data, dropped) = self.translator.data(response)?;
+                for field in dropped {
+                    warn!(
+                        "Left out of {}: {}",
+                        &self.request_description.source_name, field
+                    );
+                }
+                for point in data.values_mut().flatten() {
+                    for (name, value) in &self.request_description.labels {
+                        point
+                            .labels
+                            .entry(name.clone())
+                            .or_insert_with(|| value.clone());
+                    }
+                }
+                Ok(Translated::Data(data))
             }
             ProducerAction::STOREVARIABLE => {
-                varstore::store_variable(&self.request_description.storage_var, &self.translate_for_variable_store(&response));
+                Ok(Translated::Variables(self.translator.variables(response)?))
             }
         }
     }
+
+    fn response_action(&self, translated: Translated) -> Vec<DataResponse> {
+        match translated {
+            Translated::Data(data) => {
+                to_data_responses(&self.request_description.source_name, data)
+            }
+            Translated::Variables(pairs) => {
+                varstore::store_variable(&self.request_description.storage_var, &pairs);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Request with variables swapped in, the same for every page
+struct PreparedRequest {
+    api_url: String,
+    body: Option<(String, Mime)>,
+    headers: Vec<(String, String)>,
+}
+
+/// Results of a response translated for the response action
+enum Translated {
+    Data(DataSource),
+    Variables(VarPairs),
+}
+
+impl Translated {
+    fn is_empty(&self) -> bool {
+        match self {
+            Translated::Data(data) => data.is_empty(),
+            Translated::Variables(pairs) => pairs.is_empty(),
+        }
+    }
+
+    /// add the results of another page
+    fn merge(&mut self, other: Translated) {
+        match (self, other) {
+            (Translated::Data(data), Translated::Data(more)) => {
+                for (measure_name, values) in more {
+                    data.entry(measure_name).or_default().extend(values);
+                }
+            }
+            (Translated::Variables(pairs), Translated::Variables(more)) => pairs.extend(more),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::messages::{MeasureValue, ProducerError};
+    use crate::actors::producer::publishdata::timestamp_now;
+    use crate::actors::producer::{
+        ApiKeyLocation, ApiRequestType, ConditionalRequest, NextPage, OAuth2Config, OAuth2Grant,
+        Pagination, RegexCaptures, RequestLimits,
+    };
+    use crate::jsonutility::Flatten;
+    use crate::schedule::ScheduleType;
+    use crate::testserver::{TestResponse, TestServer};
+    use crate::{Labels, SharedVar};
+    use std::collections::HashMap;
+    use std::sync::RwLock;
+
+    const DATA: &str = r#"[{"measure_name": "A", "measure_data": {"price": 1.5}}]"#;
+
+    fn schedule(url: &str, storage_var: SharedVar) -> WebProducerSchedule {
+        WebProducerSchedule {
+            source_name: String::from("TEST"),
+            api_url: url.to_owned(),
+            request_type: ApiRequestType::GET,
+            body: None,
+            headers: Vec::new(),
+            auth: None,
+            rate_limit: None,
+            schedule: ScheduleType::Cron(String::from("0 0 0 1 1 *")),
+            calendar: None,
+            timezone: None,
+            jmespatch_query: String::from("@"),
+            format: ResponseFormat::Json,
+            lenient: false,
+            flatten: Flatten::default(),
+            labels: Labels::new(),
+            retry: None,
+            limits: RequestLimits::default(),
+            conditional: ConditionalRequest::default(),
+            pagination: None,
+            depends_on: Vec::new(),
+            storage_var,
+            response_action: ProducerAction::PUBLISHDATA,
+        }
+    }
+
+    #[async_std::test]
+    async fn variables_swapped_in_every_header() {
+        let server = TestServer::start(vec![TestResponse::ok(DATA)]).await;
+        let storage_var = Arc::new(RwLock::new(HashMap::new()));
+        {
+            let mut storage = storage_var.write().unwrap();
+            storage.insert(String::from("TOKEN"), String::from("abc"));
+            storage.insert(String::from("ACCOUNT"), String::from("42"));
+        }
+        let mut request = schedule(&server.url, storage_var);
+        request.headers = vec![
+            (
+                String::from("Authorization"),
+                String::from("Bearer [[TOKEN]]"),
+            ),
+            (String::from("X-Account"), String::from("[[ACCOUNT]]")),
+        ];
+        let mut producer = WebProducer::new(request).unwrap();
+
+        let data = producer.run_once().await.unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].measure_value, MeasureValue::Float(1.5));
+        let request = &server.requests()[0];
+        assert_eq!(request.header("authorization"), Some("Bearer abc"));
+        assert_eq!(request.header("x-account"), Some("42"));
+    }
+
+    #[test]
+    fn headers_prepared_in_schedule_order() {
+        let storage_var = Arc::new(RwLock::new(HashMap::new()));
+        storage_var
+            .write()
+            .unwrap()
+            .insert(String::from("TOKEN"), String::from("abc"));
+        let mut request = schedule("http://localhost/unused", storage_var);
+        let header = |name: &str, value: &str| (String::from(name), String::from(value));
+        request.headers = vec![
+            header("X-Trace", "1"),
+            header("User-Agent", "data-watch"),
+            header("Authorization", "Bearer [[TOKEN]]"),
+            header("Accept", "application/json"),
+        ];
+        let producer = WebProducer::new(request).unwrap();
+
+        assert_eq!(
+            producer.build_request().unwrap().headers,
+            vec![
+                header("X-Trace", "1"),
+                header("User-Agent", "data-watch"),
+                header("Authorization", "Bearer abc"),
+                header("Accept", "application/json"),
+            ]
+        );
+    }
+
+    #[async_std::test]
+    async fn api_key_added_to_query() {
+        let server = TestServer::start(vec![TestResponse::ok(DATA)]).await;
+        let storage_var = Arc::new(RwLock::new(HashMap::new()));
+        storage_var
+            .write()
+            .unwrap()
+            .insert(String::from("KEY"), String::from("secret"));
+        let mut request = schedule(&format!("{}/data?q=1", server.url), storage_var);
+        request.auth = Some(RequestAuth::ApiKey {
+            location: ApiKeyLocation::Query(String::from("appid")),
+            key_var: String::from("KEY"),
+        });
+        let mut producer = WebProducer::new(request).unwrap();
+
+        assert_eq!(producer.run_once().await.unwrap().len(), 1);
+        assert!(server.requests()[0]
+            .head
+            .starts_with("GET /data?q=1&appid=secret "));
+    }
+
+    #[async_std::test]
+    async fn oauth2_token_renewed_after_401() {
+        let token_server = TestServer::start(vec![
+            TestResponse::ok(r#"{"access_token": "one", "expires_in": 3600}"#),
+            TestResponse::ok(r#"{"access_token": "two", "expires_in": 3600}"#),
+        ])
+        .await;
+        let server =
+            TestServer::start(vec![TestResponse::status(401), TestResponse::ok(DATA)]).await;
+        let storage_var: SharedVar = Arc::new(RwLock::new(HashMap::new()));
+        let provider = Arc::new(OAuth2Provider::new(OAuth2Config {
+            name: String::from("TEST"),
+            token_url: token_server.url.clone(),
+            grant: OAuth2Grant::ClientCredentials,
+            client_id: String::from("data-watch"),
+            client_secret: None,
+            scope: None,
+            refresh_margin: Duration::from_secs(60),
+            storage_var: storage_var.clone(),
+        }));
+        let mut request = schedule(&server.url, storage_var);
+        request.auth = Some(RequestAuth::OAuth2(String::from("TEST")));
+        let mut producer = WebProducer::new(request)
+            .unwrap()
+            .with_auth_provider(provider);
+
+        assert_eq!(producer.run_once().await.unwrap().len(), 1);
+        let requests = server.requests();
+        assert_eq!(requests[0].header("authorization"), Some("Bearer one"));
+        assert_eq!(requests[1].header("authorization"), Some("Bearer two"));
+        assert_eq!(token_server.requests().len(), 2);
+    }
+
+    #[async_std::test]
+    async fn lenient_response_published_without_bad_values() {
+        let server = TestServer::start(vec![TestResponse::ok(
+            r#"{"measure_name": "BTC-USD", "measure_data": {"price": "43000.12", "bid": null}}"#,
+        )])
+        .await;
+        let mut request = schedule(&server.url, Arc::new(RwLock::new(HashMap::new())));
+        request.lenient = true;
+        let mut producer = WebProducer::new(request).unwrap();
+
+        let data = producer.run_once().await.unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].measure_value, MeasureValue::Float(43000.12));
+    }
+
+    #[test]
+    fn regex_without_measure_name_rejected() {
+        let mut request = schedule(
+            "http://localhost/unused",
+            Arc::new(RwLock::new(HashMap::new())),
+        );
+        // misspelled group, so no match names its measure
+        request.format = ResponseFormat::Regex(RegexCaptures {
+            pattern: String::from(r"(?P<measure>\w+) (?P<last>\S+)"),
+            measure_name: None,
+        });
+        assert!(matches!(
+            WebProducer::new(request.clone()),
+            Err(DataWatchError::Config(_))
+        ));
+        request.response_action = ProducerAction::STOREVARIABLE;
+        assert!(WebProducer::new(request).is_ok());
+    }
+
+    #[async_std::test]
+    async fn unchanged_response_skipped() {
+        let server = TestServer::start(vec![
+            TestResponse::ok(DATA)
+                .with_header("ETag", "\"v1\"")
+                .with_header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+            TestResponse::status(304),
+        ])
+        .await;
+        let mut request = schedule(&server.url, Arc::new(RwLock::new(HashMap::new())));
+        request.conditional = ConditionalRequest::Skip;
+        let mut producer = WebProducer::new(request).unwrap();
+
+        assert_eq!(producer.run_once().await.unwrap().len(), 1);
+        assert!(producer.run_once().await.unwrap().is_empty());
+        let requests = server.requests();
+        assert_eq!(requests[0].header("if-none-match"), None);
+        assert_eq!(requests[1].header("if-none-match"), Some("\"v1\""));
+        assert_eq!(
+            requests[1].header("if-modified-since"),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+    }
+
+    #[async_std::test]
+    async fn unchanged_response_republished() {
+        let server = TestServer::start(vec![
+            TestResponse::ok(DATA).with_header("ETag", "\"v1\""),
+            TestResponse::status(304),
+        ])
+        .await;
+        let mut request = schedule(&server.url, Arc::new(RwLock::new(HashMap::new())));
+        request.conditional = ConditionalRequest::Republish;
+        let mut producer = WebProducer::new(request).unwrap();
+
+        let first = producer.run_once().await.unwrap();
+        let again = producer.run_once().await.unwrap();
+        assert_eq!(again.len(), 1);
+        assert_eq!(again[0].measure_name, first[0].measure_name);
+        assert_eq!(again[0].measure_value, MeasureValue::Float(1.5));
+        assert_eq!(server.requests()[1].header("if-none-match"), Some("\"v1\""));
+    }
+
+    #[async_std::test]
+    async fn republished_with_fresh_timestamp() {
+        let server = TestServer::start(vec![
+            TestResponse::ok(
+                r#"{"measure_name": "SPY", "measure_data": {"price": 1.5}, "timestamp": "2021-05-03T00:00:00Z"}"#,
+            )
+            .with_header("ETag", "\"v1\""),
+            TestResponse::status(304),
+        ])
+        .await;
+        let mut request = schedule(&server.url, Arc::new(RwLock::new(HashMap::new())));
+        request.conditional = ConditionalRequest::Republish;
+        let mut producer = WebProducer::new(request).unwrap();
+
+        assert_eq!(producer.run_once().await.unwrap()[0].timestamp, 1620000000);
+        let now = timestamp_now();
+        assert!(producer.run_once().await.unwrap()[0].timestamp >= now);
+    }
+
+    #[async_std::test]
+    async fn schedule_labels_added_to_data() {
+        let server = TestServer::start(vec![TestResponse::ok(
+            r#"{"measure_name": "SPY", "measure_data": {"price": 1.5}, "labels": {"exchange": "ARCA"}}"#,
+        )])
+        .await;
+        let mut request = schedule(&server.url, Arc::new(RwLock::new(HashMap::new())));
+        request
+            .labels
+            .insert(String::from("exchange"), String::from("NYSE"));
+        request
+            .labels
+            .insert(String::from("currency"), String::from("USD"));
+        let mut producer = WebProducer::new(request).unwrap();
+
+        let labels = &producer.run_once().await.unwrap()[0].labels;
+        assert_eq!(labels["exchange"], "ARCA");
+        assert_eq!(labels["currency"], "USD");
+    }
+
+    #[async_std::test]
+    async fn validators_kept_only_after_parse() {
+        let server = TestServer::start(vec![
+            TestResponse::ok("not json").with_header("ETag", "\"bad\""),
+            TestResponse::ok(DATA),
+        ])
+        .await;
+        let mut request = schedule(&server.url, Arc::new(RwLock::new(HashMap::new())));
+        request.conditional = ConditionalRequest::Skip;
+        let mut producer = WebProducer::new(request).unwrap();
+
+        assert!(producer.run_once().await.is_err());
+        assert_eq!(producer.run_once().await.unwrap().len(), 1);
+        assert_eq!(server.requests()[1].header("if-none-match"), None);
+    }
+
+    #[async_std::test]
+    async fn pages_merged_until_no_next_link() {
+        let server = TestServer::start(vec![
+            TestResponse::ok(
+                r#"{"items": [{"measure_name": "A", "measure_data": {"price": 1.5}}], "next": "/items?page=2"}"#,
+            ),
+            TestResponse::ok(
+                r#"{"items": [{"measure_name": "A", "measure_data": {"volume": 3}}, {"measure_name": "B", "measure_data": {"price": 2}}], "next": null}"#,
+            ),
+        ])
+        .await;
+        let storage_var = Arc::new(RwLock::new(HashMap::new()));
+        storage_var
+            .write()
+            .unwrap()
+            .insert(String::from("KEY"), String::from("secret"));
+        let mut request = schedule(&format!("{}/items", server.url), storage_var);
+        request.jmespatch_query = String::from("items");
+        request.auth = Some(RequestAuth::ApiKey {
+            location: ApiKeyLocation::Query(String::from("key")),
+            key_var: String::from("KEY"),
+        });
+        request.pagination = Some(Pagination {
+            next: NextPage::Link(String::from("next")),
+            max_pages: 5,
+        });
+        let mut producer = WebProducer::new(request).unwrap();
+
+        let mut data: Vec<_> = producer
+            .run_once()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|data| (data.measure_name, data.measure_desc, data.measure_value))
+            .collect();
+        data.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        let point = |name: &str, desc: &str, value| (name.to_owned(), desc.to_owned(), value);
+        assert_eq!(
+            data,
+            vec![
+                point("A", "price", MeasureValue::Float(1.5)),
+                point("A", "volume", MeasureValue::Integer(3)),
+                point("B", "price", MeasureValue::Integer(2)),
+            ]
+        );
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].head.starts_with("GET /items?key=secret "));
+        assert!(requests[1]
+            .head
+            .starts_with("GET /items?page=2&key=secret "));
+    }
+
+    #[async_std::test]
+    async fn pages_limited_by_max_pages() {
+        let server = TestServer::start(vec![TestResponse::ok(DATA)]).await;
+        let mut request = schedule(&server.url, Arc::new(RwLock::new(HashMap::new())));
+        request.pagination = Some(Pagination {
+            next: NextPage::Offset {
+                param: String::from("page"),
+                start: 1,
+                step: 1,
+            },
+            max_pages: 3,
+        });
+        let mut producer = WebProducer::new(request).unwrap();
+
+        assert_eq!(producer.run_once().await.unwrap().len(), 3);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].head.starts_with("GET /?page=3 "));
+    }
+
+    /// collects `ProducerError` messages published on the broker
+    struct ErrorCollector(Arc<std::sync::Mutex<Vec<ProducerError>>>);
+
+    #[async_trait]
+    impl Actor for ErrorCollector {
+        async fn started(&mut self, ctx: &mut Context<Self>) -> Result<()> {
+            ctx.subscribe::<ProducerError>().await
+        }
+    }
+
+    #[async_trait]
+    impl Handler<ProducerError> for ErrorCollector {
+        async fn handle(&mut self, _ctx: &mut Context<Self>, msg: ProducerError) {
+            self.0.lock().unwrap().push(msg);
+        }
+    }
+
+    #[async_std::test]
+    async fn error_status_published_with_response() {
+        let errors = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let _collector = ErrorCollector(errors.clone()).start().await.unwrap();
+        let server = TestServer::start(vec![TestResponse::status(503)]).await;
+        let mut request = schedule(&server.url, Arc::new(RwLock::new(HashMap::new())));
+        request.source_name = String::from("STATUS_TEST");
+        let producer = WebProducer::new(request).unwrap().start().await.unwrap();
+        producer.send(RunNow).unwrap();
+
+        let mut published = None;
+        for _ in 0..50 {
+            published = errors
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|error| error.source_name == "STATUS_TEST")
+                .cloned();
+            if published.is_some() {
+                break;
+            }
+            task::sleep(Duration::from_millis(20)).await;
+        }
+        let published = published.expect("ProducerError published");
+        assert_eq!(published.error, DataWatchError::Status(503));
+        assert_eq!(published.response.unwrap().status, 503);
+        let stats = producer.call(Stats).await.unwrap();
+        assert_eq!(stats.last_response.unwrap().status, 503);
+    }
+
+    #[async_std::test]
+    async fn oauth2_without_provider() {
+        let storage_var = Arc::new(RwLock::new(HashMap::new()));
+        let mut request = schedule("http://localhost/unused", storage_var);
+        request.auth = Some(RequestAuth::OAuth2(String::from("MISSING")));
+        let mut producer = WebProducer::new(request).unwrap();
+        assert!(matches!(
+            producer.run_once().await,
+            Err(DataWatchError::Auth(_))
+        ));
+    }
 }
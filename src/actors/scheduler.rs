@@ -1,7 +1,16 @@
-use super::messages::{Refresh, Stop, WebProducerSchedule};
-use super::producer::WebProducer;
+use super::messages::{
+    AddAuthProvider, AddCalendar, AddRateLimit, Dependency, ListSchedule, Ping, Pong,
+    ProducerStats, Refresh, RunComplete, RunNow, ScheduleStatus, StatsUpdate, Stop, StopSchedule,
+    WebProducerSchedule,
+};
+use super::producer::{publish_error, OAuth2Provider, RateLimiter, RequestAuth, WebProducer};
+use crate::calendar::Calendar;
+use crate::error::{DataWatchError, DataWatchResult};
 use async_trait::async_trait;
-use log::{debug, info};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use xactor::*;
 
 /// Scheduler
@@ -11,18 +20,92 @@ use xactor::*;
 /// - spawn actor to handle new schedule
 /// - send message to actor
 /// - store actor address and RequestSchedule message
+/// - hold the first run until `depends_on` is met, schedules depended on are run right away
+///
+/// <StatsUpdate>
+/// - keep the next fire time, retry count and last response reported by a producer
+///
+/// <RunComplete>
+/// - record successful runs and release schedules waiting on them
+/// - a 401 response re-runs the schedules depended on, then the refused request once more
 ///
 /// <StopSchedule>
+/// - stop actor(s) matching id or source_name and forget the schedule
 ///
 /// <ListSchedule>
-///
+/// - reply with id, source_name, url, next fire time, retry count and last response of each active schedule
+/// - answered from the last stats reported, a producer busy with a request is not waited for
 ///
 /// <Ping>
+/// - reply with number of active schedules
+///
+/// <AddCalendar>
+/// - store named calendar used by schedules added afterwards
+///
+/// <AddAuthProvider>
+/// - store named OAuth2 provider used by schedules added afterwards, its token cache is shared
+///
+/// <AddRateLimit>
+/// - store named rate limit used by schedules added afterwards, by name or host of their url
 
 #[derive(Default)]
 pub struct Scheduler {
-    scheduled: Vec<WebProducerSchedule>,
-    actors: Vec<Addr<WebProducer>>,
+    next_id: u64,
+    scheduled: Vec<ScheduledProducer>,
+    calendars: HashMap<String, Arc<Calendar>>,
+    auth_providers: HashMap<String, Arc<OAuth2Provider>>,
+    rate_limits: HashMap<String, Arc<RateLimiter>>,
+    /// time of the last successful run by source_name
+    completed: HashMap<String, DateTime<Utc>>,
+    /// ids of producers sent `RunNow` that have not reported back
+    triggered: HashSet<u64>,
+}
+
+/// A running schedule with the actor handling it
+struct ScheduledProducer {
+    id: u64,
+    request: WebProducerSchedule,
+    actor: Addr<WebProducer>,
+    /// last stats reported by the producer
+    stats: ProducerStats,
+    waiting: Option<Waiting>,
+    /// dependencies were re-run after a 401, cleared by the next successful run
+    reauthorized: bool,
+}
+
+/// Why a schedule is held back
+enum Waiting {
+    /// first run waits for dependencies
+    FirstRun,
+    /// request was refused with 401, runs again once dependencies complete after `since`
+    Reauthorize { since: DateTime<Utc> },
+}
+
+impl ScheduledProducer {
+    fn matches(&self, stop: &StopSchedule) -> bool {
+        match stop {
+            StopSchedule::Id(id) => self.id == *id,
+            StopSchedule::SourceName(name) => &self.request.source_name == name,
+        }
+    }
+
+    fn dependencies_met(&self, completed: &HashMap<String, DateTime<Utc>>) -> bool {
+        let since = match self.waiting {
+            Some(Waiting::Reauthorize { since }) => Some(since),
+            _ => None,
+        };
+        self.request
+            .depends_on
+            .iter()
+            .all(|dependency| match dependency {
+                Dependency::Schedule(name) => completed
+                    .get(name)
+                    .is_some_and(|done| since.is_none_or(|since| *done > since)),
+                Dependency::Variable(name) => {
+                    self.request.storage_var.read().unwrap().contains_key(name)
+                }
+            })
+    }
 }
 
 #[async_trait]
@@ -33,23 +116,259 @@ impl Actor for Scheduler {
     }
 
     async fn stopped(&mut self, _: &mut Context<Self>) {
+        for scheduled in self.scheduled.iter_mut() {
+            scheduled.actor.stop(None).ok();
+        }
         info!("Scheduler Stopped");
     }
 }
 
+impl Scheduler {
+    /// create producer for schedule with the named resources it references
+    fn build_producer(&self, request: &WebProducerSchedule) -> DataWatchResult<WebProducer> {
+        let mut producer = WebProducer::new(request.clone())?;
+        if let Some(name) = &request.calendar {
+            let calendar = self
+                .calendars
+                .get(name)
+                .ok_or_else(|| DataWatchError::Schedule(format!("unknown calendar {}", name)))?;
+            producer = producer.with_calendar(calendar.clone());
+        }
+        if let Some(RequestAuth::OAuth2(name)) = &request.auth {
+            let provider = self.auth_providers.get(name).ok_or_else(|| {
+                DataWatchError::Schedule(format!("unknown auth provider {}", name))
+            })?;
+            producer = producer.with_auth_provider(provider.clone());
+        }
+        if let Some(limiter) = RateLimiter::select(
+            &self.rate_limits,
+            request.rate_limit.as_deref(),
+            &request.api_url,
+        )? {
+            producer = producer.with_rate_limiter(limiter);
+        }
+        Ok(producer)
+    }
+
+    /// release waiting schedules whose dependencies are met, then run
+    /// the schedules that those still waiting for a first run depend on
+    fn update_dependencies(&mut self) {
+        let completed = &self.completed;
+        let mut missing = Vec::new();
+        for scheduled in self.scheduled.iter_mut() {
+            match &scheduled.waiting {
+                Some(_) if scheduled.dependencies_met(completed) => {
+                    info!(
+                        "Dependencies met for schedule {} ({})",
+                        scheduled.id, scheduled.request.source_name
+                    );
+                    if let Some(Waiting::Reauthorize { .. }) = scheduled.waiting.take() {
+                        scheduled.reauthorized = true;
+                        self.triggered.insert(scheduled.id);
+                        scheduled.actor.send(RunNow).ok();
+                    } else {
+                        scheduled.actor.send(Refresh).ok();
+                    }
+                }
+                Some(Waiting::FirstRun) => missing.extend(
+                    scheduled
+                        .request
+                        .schedule_dependencies()
+                        .filter(|name| !completed.contains_key(*name))
+                        .cloned(),
+                ),
+                _ => {}
+            }
+        }
+        self.run_now(&missing);
+    }
+
+    /// run schedules with these source names unless already triggered
+    fn run_now(&mut self, source_names: &[String]) {
+        for scheduled in self.scheduled.iter() {
+            if source_names.contains(&scheduled.request.source_name)
+                && self.triggered.insert(scheduled.id)
+            {
+                info!(
+                    "Running dependency {} ({})",
+                    scheduled.id, scheduled.request.source_name
+                );
+                scheduled.actor.send(RunNow).ok();
+            }
+        }
+    }
+}
+
 #[async_trait]
 impl Handler<WebProducerSchedule> for Scheduler {
-    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: WebProducerSchedule) {
-        debug!("message<RequestSchedule> received: {:?}", msg);
+    async fn handle(&mut self, ctx: &mut Context<Self>, msg: WebProducerSchedule) {
+        // storage_var is left out, it holds the secrets
+        debug!(
+            "message<RequestSchedule> received: {:?} {} {:?}",
+            msg.request_type, msg.api_url, msg.auth
+        );
         info!("<RequestSchedule> received: {}", msg.source_name);
         // create new actor to manage request
-        self.scheduled.push(msg.clone());
+        let producer = match self.build_producer(&msg) {
+            Ok(producer) => producer,
+            Err(e) => {
+                error!("<RequestSchedule> rejected for {}: {}", msg.source_name, e);
+                publish_error(&msg.source_name, e, None).await;
+                return;
+            }
+        };
+
+        self.next_id += 1;
+        let producer =
+            producer.with_reporter(self.next_id, ctx.address().sender(), ctx.address().sender());
+        let newactor = producer.start().await.unwrap();
+
+        let waiting = if msg.depends_on.is_empty() {
+            newactor.send(Refresh {}).unwrap();
+            None
+        } else {
+            info!("{} waits for {:?}", msg.source_name, msg.depends_on);
+            Some(Waiting::FirstRun)
+        };
+        self.scheduled.push(ScheduledProducer {
+            id: self.next_id,
+            request: msg,
+            actor: newactor,
+            stats: ProducerStats::default(),
+            waiting,
+            reauthorized: false,
+        });
+        self.update_dependencies();
+    }
+}
 
-        let newactor = WebProducer::new(msg.clone()).start().await.unwrap();
+#[async_trait]
+impl Handler<StatsUpdate> for Scheduler {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: StatsUpdate) {
+        if let Some(scheduled) = self.scheduled.iter_mut().find(|s| s.id == msg.id) {
+            scheduled.stats = msg.stats;
+        }
+    }
+}
 
-        self.actors.push(newactor.clone());
+#[async_trait]
+impl Handler<RunComplete> for Scheduler {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: RunComplete) {
+        debug!("<RunComplete> received: {:?}", msg);
+        self.triggered.remove(&msg.id);
+        let mut rerun = Vec::new();
+        if let Some(scheduled) = self.scheduled.iter_mut().find(|s| s.id == msg.id) {
+            match msg.error {
+                None => {
+                    scheduled.reauthorized = false;
+                    self.completed
+                        .insert(scheduled.request.source_name.clone(), Utc::now());
+                }
+                Some(DataWatchError::Status(401))
+                    if scheduled.waiting.is_none()
+                        && scheduled.request.schedule_dependencies().next().is_some() =>
+                {
+                    if scheduled.reauthorized {
+                        warn!(
+                            "{} still refused after re-running dependencies",
+                            scheduled.request.source_name
+                        );
+                    } else {
+                        info!(
+                            "{} refused, re-running dependencies",
+                            scheduled.request.source_name
+                        );
+                        scheduled.waiting = Some(Waiting::Reauthorize { since: Utc::now() });
+                        rerun.extend(scheduled.request.schedule_dependencies().cloned());
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+        self.run_now(&rerun);
+        self.update_dependencies();
+    }
+}
 
-        newactor.send(Refresh {}).unwrap();
+#[async_trait]
+impl Handler<AddCalendar> for Scheduler {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: AddCalendar) {
+        info!("<AddCalendar> received: {}", msg.0.name);
+        self.calendars.insert(msg.0.name.clone(), Arc::new(msg.0));
+    }
+}
+
+#[async_trait]
+impl Handler<AddAuthProvider> for Scheduler {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: AddAuthProvider) {
+        info!("<AddAuthProvider> received: {}", msg.0.name);
+        self.auth_providers
+            .insert(msg.0.name.clone(), Arc::new(OAuth2Provider::new(msg.0)));
+    }
+}
+
+#[async_trait]
+impl Handler<AddRateLimit> for Scheduler {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: AddRateLimit) {
+        info!("<AddRateLimit> received: {}", msg.0.name);
+        self.rate_limits
+            .insert(msg.0.name.clone(), Arc::new(RateLimiter::new(msg.0)));
+    }
+}
+
+#[async_trait]
+impl Handler<StopSchedule> for Scheduler {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: StopSchedule) {
+        info!("<StopSchedule> received: {:?}", msg);
+        let (mut stopping, running): (Vec<_>, Vec<_>) = self
+            .scheduled
+            .drain(..)
+            .partition(|scheduled| scheduled.matches(&msg));
+        self.scheduled = running;
+        if stopping.is_empty() {
+            info!("<StopSchedule> no schedule matched {:?}", msg);
+        }
+        for scheduled in stopping.iter_mut() {
+            self.triggered.remove(&scheduled.id);
+            info!(
+                "Stopping schedule {} for {}",
+                scheduled.id, scheduled.request.source_name
+            );
+            scheduled.actor.stop(None).ok();
+        }
+    }
+}
+
+#[async_trait]
+impl Handler<ListSchedule> for Scheduler {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: ListSchedule,
+    ) -> Vec<ScheduleStatus> {
+        debug!("<ListSchedule> received");
+        self.scheduled
+            .iter()
+            .map(|scheduled| ScheduleStatus {
+                id: scheduled.id,
+                source_name: scheduled.request.source_name.clone(),
+                api_url: scheduled.request.api_url.clone(),
+                next_run: scheduled.stats.next_run,
+                retries: scheduled.stats.retries,
+                last_response: scheduled.stats.last_response.clone(),
+                waiting: scheduled.waiting.is_some(),
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Handler<Ping> for Scheduler {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: Ping) -> Pong {
+        debug!("<Ping> received");
+        Pong {
+            active_schedules: self.scheduled.len(),
+        }
     }
 }
 
@@ -60,3 +379,329 @@ impl Handler<Stop> for Scheduler {
         ctx.stop(None);
     }
 }
+
+/// Positions of `schedules` ordered so each comes after the schedules it depends on,
+/// otherwise in the order given
+///
+/// Returns a config error naming the schedules when they depend on each other,
+/// unknown source names are not checked
+pub fn dependency_order(schedules: &[WebProducerSchedule]) -> DataWatchResult<Vec<usize>> {
+    let mut positions = HashMap::new();
+    for (position, schedule) in schedules.iter().enumerate().rev() {
+        positions.insert(schedule.source_name.as_str(), position);
+    }
+    let mut order = Vec::new();
+    let mut path = Vec::new();
+    for position in 0..schedules.len() {
+        visit_dependencies(schedules, &positions, position, &mut path, &mut order)?;
+    }
+    Ok(order)
+}
+
+/// add `position` to `order` after its dependencies, `path` holds the schedules being visited
+fn visit_dependencies(
+    schedules: &[WebProducerSchedule],
+    positions: &HashMap<&str, usize>,
+    position: usize,
+    path: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> DataWatchResult<()> {
+    if order.contains(&position) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|visiting| *visiting == position) {
+        let cycle: Vec<&str> = path[start..]
+            .iter()
+            .chain(Some(&position))
+            .map(|visiting| schedules[*visiting].source_name.as_str())
+            .collect();
+        return Err(DataWatchError::Config(format!(
+            "schedules depend on each other: {}",
+            cycle.join(" -> ")
+        )));
+    }
+    path.push(position);
+    for name in schedules[position].schedule_dependencies() {
+        if let Some(dependency) = positions.get(name.as_str()) {
+            visit_dependencies(schedules, positions, *dependency, path, order)?;
+        }
+    }
+    path.pop();
+    order.push(position);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::producer::{
+        ApiRequestType, ConditionalRequest, OAuth2Config, OAuth2Grant, ProducerAction, RequestBody,
+        RequestLimits, ResponseFormat,
+    };
+    use crate::jsonutility::Flatten;
+    use crate::schedule::ScheduleType;
+    use crate::testserver::{TestResponse, TestServer};
+    use crate::{Labels, SharedVar};
+    use async_std::future::timeout;
+    use async_std::net::TcpListener;
+    use async_std::task;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::RwLock;
+    use std::time::Duration;
+
+    fn request(source_name: &str) -> WebProducerSchedule {
+        WebProducerSchedule {
+            source_name: String::from(source_name),
+            api_url: String::from("http://localhost/unused"),
+            request_type: ApiRequestType::GET,
+            body: None,
+            headers: Vec::new(),
+            auth: None,
+            rate_limit: None,
+            // first of january only, so nothing fires during the test
+            schedule: ScheduleType::Cron(String::from("0 0 0 1 1 *")),
+            jmespatch_query: String::from("@"),
+            format: ResponseFormat::Json,
+            lenient: false,
+            flatten: Flatten::default(),
+            labels: Labels::new(),
+            retry: None,
+            limits: RequestLimits::default(),
+            conditional: ConditionalRequest::default(),
+            pagination: None,
+            depends_on: Vec::new(),
+            calendar: None,
+            timezone: None,
+            storage_var: Arc::new(RwLock::new(HashMap::new())),
+            response_action: ProducerAction::PUBLISHDATA,
+        }
+    }
+
+    #[test]
+    fn schedules_ordered_by_dependencies() {
+        let depends = |source_name: &str, on: &[&str]| {
+            let mut schedule = request(source_name);
+            schedule.depends_on = on
+                .iter()
+                .map(|name| Dependency::Schedule(name.to_string()))
+                .collect();
+            schedule
+        };
+        let schedules = vec![
+            depends("QUOTE", &["AUTH", "ACCOUNT"]),
+            depends("ACCOUNT", &["AUTH"]),
+            depends("AUTH", &["ELSEWHERE"]),
+            depends("NEWS", &[]),
+        ];
+        assert_eq!(dependency_order(&schedules).unwrap(), vec![2, 1, 0, 3]);
+
+        let cycle = vec![
+            depends("NEWS", &[]),
+            depends("A", &["B"]),
+            depends("B", &["A"]),
+        ];
+        let error = dependency_order(&cycle).unwrap_err().to_string();
+        assert!(error.contains("schedules depend on each other: A -> B -> A"));
+    }
+
+    /// poll until `condition` holds, fail after 5 seconds
+    async fn wait_until<F: Fn() -> bool>(condition: F) {
+        for _ in 0..50 {
+            if condition() {
+                return;
+            }
+            task::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    /// schedule storing TOKEN from a token server
+    fn auth_request(server: &TestServer, storage_var: &SharedVar) -> WebProducerSchedule {
+        let mut auth = request("AUTH");
+        auth.api_url = server.url.clone();
+        auth.request_type = ApiRequestType::POST;
+        auth.body = Some(RequestBody::Form(String::from("grant_type=refresh_token")));
+        auth.jmespatch_query = String::from("{ TOKEN: token }");
+        auth.response_action = ProducerAction::STOREVARIABLE;
+        auth.storage_var = storage_var.clone();
+        auth
+    }
+
+    #[async_std::test]
+    async fn list_and_stop_schedules() {
+        let scheduler = Scheduler::default().start().await.unwrap();
+        scheduler.send(request("ONE")).unwrap();
+        scheduler.send(request("TWO")).unwrap();
+        scheduler.send(request("TWO")).unwrap();
+
+        let mut list = scheduler.call(ListSchedule).await.unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].id, 1);
+        assert_eq!(list[0].source_name, "ONE");
+        // next fire times are reported by the producers once scheduled
+        for _ in 0..50 {
+            if list.iter().all(|status| status.next_run.is_some()) {
+                break;
+            }
+            task::sleep(Duration::from_millis(100)).await;
+            list = scheduler.call(ListSchedule).await.unwrap();
+        }
+        assert!(list.iter().all(|status| status.next_run.is_some()));
+
+        scheduler
+            .send(StopSchedule::SourceName(String::from("TWO")))
+            .unwrap();
+        assert_eq!(scheduler.call(Ping).await.unwrap().active_schedules, 1);
+
+        scheduler.send(StopSchedule::Id(1)).unwrap();
+        assert!(scheduler.call(ListSchedule).await.unwrap().is_empty());
+    }
+
+    #[async_std::test]
+    async fn invalid_schedule_is_not_started() {
+        let scheduler = Scheduler::default().start().await.unwrap();
+        let mut invalid = request("INVALID");
+        invalid.schedule = ScheduleType::Cron(String::from("not a cron"));
+        scheduler.send(invalid).unwrap();
+        assert_eq!(scheduler.call(Ping).await.unwrap().active_schedules, 0);
+    }
+
+    #[async_std::test]
+    async fn list_while_producer_busy() {
+        // server that accepts the request and never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let accepted = Arc::new(AtomicBool::new(false));
+        let server_accepted = accepted.clone();
+        task::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            server_accepted.store(true, Ordering::SeqCst);
+            task::sleep(Duration::from_secs(10)).await;
+        });
+
+        let scheduler = Scheduler::default().start().await.unwrap();
+        let mut stalled = request("STALLED");
+        stalled.api_url = url;
+        stalled.schedule = ScheduleType::Interval {
+            every: Duration::from_secs(60),
+            offset: None,
+        };
+        stalled.limits = RequestLimits {
+            connect_timeout: None,
+            header_timeout: None,
+            total_timeout: None,
+            max_body_size: None,
+        };
+        scheduler.send(stalled).unwrap();
+        wait_until(|| accepted.load(Ordering::SeqCst)).await;
+
+        let list = timeout(Duration::from_secs(1), scheduler.call(ListSchedule))
+            .await
+            .expect("ListSchedule answered while the producer waits")
+            .unwrap();
+        assert_eq!(list[0].source_name, "STALLED");
+        assert!(list[0].next_run.is_some());
+    }
+
+    #[async_std::test]
+    async fn schedule_with_calendar() {
+        let scheduler = Scheduler::default().start().await.unwrap();
+        let mut holiday = request("HOLIDAY");
+        holiday.calendar = Some(String::from("NYSE"));
+        scheduler.send(holiday.clone()).unwrap();
+        assert_eq!(scheduler.call(Ping).await.unwrap().active_schedules, 0);
+
+        scheduler
+            .send(AddCalendar(Calendar::parse("NYSE", "2021-01-01").unwrap()))
+            .unwrap();
+        scheduler.send(holiday).unwrap();
+        assert_eq!(scheduler.call(Ping).await.unwrap().active_schedules, 1);
+    }
+
+    #[async_std::test]
+    async fn schedule_with_auth_provider() {
+        let scheduler = Scheduler::default().start().await.unwrap();
+        let mut broker = request("BROKER");
+        broker.auth = Some(RequestAuth::OAuth2(String::from("TD")));
+        scheduler.send(broker.clone()).unwrap();
+        assert_eq!(scheduler.call(Ping).await.unwrap().active_schedules, 0);
+
+        scheduler
+            .send(AddAuthProvider(OAuth2Config {
+                name: String::from("TD"),
+                token_url: String::from("http://localhost/unused"),
+                grant: OAuth2Grant::ClientCredentials,
+                client_id: String::from("data-watch"),
+                client_secret: None,
+                scope: None,
+                refresh_margin: Duration::from_secs(60),
+                storage_var: Arc::new(RwLock::new(HashMap::new())),
+            }))
+            .unwrap();
+        scheduler.send(broker).unwrap();
+        assert_eq!(scheduler.call(Ping).await.unwrap().active_schedules, 1);
+    }
+
+    #[async_std::test]
+    async fn first_run_waits_for_dependencies() {
+        let auth_server = TestServer::start(vec![TestResponse::ok(r#"{"token": "abc"}"#)]).await;
+        let storage_var: SharedVar = Arc::new(RwLock::new(HashMap::new()));
+        let scheduler = Scheduler::default().start().await.unwrap();
+
+        let mut quote = request("QUOTE");
+        quote.depends_on = vec![
+            Dependency::Schedule(String::from("AUTH")),
+            Dependency::Variable(String::from("TOKEN")),
+        ];
+        quote.storage_var = storage_var.clone();
+        scheduler.send(quote).unwrap();
+        let list = scheduler.call(ListSchedule).await.unwrap();
+        assert!(list[0].waiting);
+
+        // adding the schedule depended on runs it right away
+        scheduler
+            .send(auth_request(&auth_server, &storage_var))
+            .unwrap();
+        wait_until(|| storage_var.read().unwrap().contains_key("TOKEN")).await;
+        for _ in 0..50 {
+            if !scheduler.call(ListSchedule).await.unwrap()[0].waiting {
+                break;
+            }
+            task::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(!scheduler.call(ListSchedule).await.unwrap()[0].waiting);
+        let auth_requests = auth_server.requests();
+        assert_eq!(auth_requests.len(), 1);
+        assert!(auth_requests[0].head.starts_with("POST / "));
+        assert_eq!(auth_requests[0].body, "grant_type=refresh_token");
+    }
+
+    #[async_std::test]
+    async fn unauthorized_reruns_dependency() {
+        let auth_server = TestServer::start(vec![TestResponse::ok(r#"{"token": "abc"}"#)]).await;
+        let quote_server = TestServer::start(vec![
+            TestResponse::status(401),
+            TestResponse::ok(r#"[{"measure_name": "A", "measure_data": {"price": 1.0}}]"#),
+        ])
+        .await;
+        let storage_var: SharedVar = Arc::new(RwLock::new(HashMap::new()));
+        let scheduler = Scheduler::default().start().await.unwrap();
+        scheduler
+            .send(auth_request(&auth_server, &storage_var))
+            .unwrap();
+
+        let mut quote = request("QUOTE");
+        quote.api_url = quote_server.url.clone();
+        quote.schedule = ScheduleType::Interval {
+            every: Duration::from_secs(1),
+            offset: None,
+        };
+        quote.depends_on = vec![Dependency::Schedule(String::from("AUTH"))];
+        quote.storage_var = storage_var.clone();
+        scheduler.send(quote).unwrap();
+
+        // first token, then 401 on the first quote renews token and repeats the quote
+        wait_until(|| quote_server.requests().len() >= 2).await;
+        assert_eq!(auth_server.requests().len(), 2);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::error::{DataWatchError, DataWatchResult};
+use chrono::{Duration, NaiveDate};
+use std::fs;
+use std::path::Path;
+
+/// Named set of dates on which a schedule does not fire, like market holidays
+///
+/// Calendar file has one date or date range per line, blank lines and `#` comments are ignored
+///
+/// ```text
+/// # NYSE holidays
+/// 2021-01-01
+/// 2021-12-24..2021-12-26
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calendar {
+    pub name: String,
+    excluded: Vec<(NaiveDate, NaiveDate)>,
+}
+
+impl Calendar {
+    pub fn new(name: &str) -> Self {
+        Calendar {
+            name: name.to_owned(),
+            excluded: Vec::new(),
+        }
+    }
+
+    /// load calendar from file in the format above
+    pub fn from_file<P: AsRef<Path>>(name: &str, path: P) -> DataWatchResult<Self> {
+        let text = fs::read_to_string(path.as_ref()).map_err(|e| {
+            DataWatchError::Schedule(format!(
+                "calendar {} could not be read from {}: {}",
+                name,
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        Calendar::parse(name, &text)
+    }
+
+    /// parse calendar from text in the format above
+    pub fn parse(name: &str, text: &str) -> DataWatchResult<Self> {
+        let mut calendar = Calendar::new(name);
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |reason: &str| {
+                DataWatchError::Schedule(format!(
+                    "calendar {} line {}: {} '{}'",
+                    name,
+                    number + 1,
+                    reason,
+                    line
+                ))
+            };
+            let mut range = line.splitn(2, "..");
+            let start = parse_date(range.next().unwrap_or_default())
+                .ok_or_else(|| invalid("invalid date"))?;
+            let end = match range.next() {
+                Some(end) => parse_date(end).ok_or_else(|| invalid("invalid date"))?,
+                None => start,
+            };
+            if end < start {
+                return Err(invalid("range ends before it starts"));
+            }
+            calendar.exclude(start, end);
+        }
+        Ok(calendar)
+    }
+
+    /// exclude every date from start to end, inclusive
+    pub fn exclude(&mut self, start: NaiveDate, end: NaiveDate) {
+        self.excluded.push((start, end));
+    }
+
+    pub fn is_excluded(&self, date: NaiveDate) -> bool {
+        self.excluded
+            .iter()
+            .any(|(start, end)| *start <= date && date <= *end)
+    }
+
+    /// first date on or after `date` that is not excluded
+    pub fn next_open_date(&self, date: NaiveDate) -> NaiveDate {
+        let mut date = date;
+        while let Some((_, end)) = self
+            .excluded
+            .iter()
+            .find(|(start, end)| *start <= date && date <= *end)
+        {
+            date = *end + Duration::days(1);
+        }
+        date
+    }
+}
+
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dates_and_ranges() {
+        let text = "
+            # market holidays
+            2021-01-01
+            2021-12-24..2021-12-26  # christmas
+            2021-12-27..2021-12-27
+        ";
+        let calendar = Calendar::parse("NYSE", text).unwrap();
+        assert!(calendar.is_excluded(NaiveDate::from_ymd(2021, 1, 1)));
+        assert!(!calendar.is_excluded(NaiveDate::from_ymd(2021, 1, 2)));
+        assert!(calendar.is_excluded(NaiveDate::from_ymd(2021, 12, 25)));
+        assert_eq!(
+            calendar.next_open_date(NaiveDate::from_ymd(2021, 12, 24)),
+            NaiveDate::from_ymd(2021, 12, 28)
+        );
+        assert_eq!(
+            calendar.next_open_date(NaiveDate::from_ymd(2021, 12, 23)),
+            NaiveDate::from_ymd(2021, 12, 23)
+        );
+    }
+
+    #[test]
+    fn parse_invalid_lines() {
+        assert!(Calendar::parse("BAD", "2021-13-01").is_err());
+        assert!(Calendar::parse("BAD", "2021-12-26..2021-12-24").is_err());
+        assert!(Calendar::parse("BAD", "christmas").is_err());
+    }
+}
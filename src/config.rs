@@ -0,0 +1,1158 @@
+use crate::actors::dependency_order;
+use crate::actors::messages::{
+    AddAuthProvider, AddCalendar, AddRateLimit, Dependency, WebProducerSchedule,
+};
+use crate::actors::producer::{
+    ApiRequestType, ClientConfig, ConditionalRequest, CsvColumns, NextPage, OAuth2Config,
+    OAuth2Grant, Pagination, ProducerAction, Rate, RateLimitConfig, RegexCaptures, RequestAuth,
+    RequestBody, RequestLimits, ResponseFormat, RetryPolicy, WebProducer, XmlFields, REDACTED,
+};
+use crate::calendar::Calendar;
+use crate::error::{DataWatchError, DataWatchResult};
+use crate::jsonutility::Flatten;
+use crate::schedule::ScheduleType;
+use crate::{Labels, SharedVar};
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Configuration file (TOML) describing shared variables, calendars, consumers and schedules
+///
+/// Relative paths are relative to the directory of the configuration file
+///
+/// ```toml
+/// [variables]
+/// TDQUOTESYMBOLS = "TRP,INTC,SPY"
+/// TDREFRESHTOKEN = { env = "TDREFRESHTOKEN" }
+/// REGION = { env = "REGION", default = "us" }
+///
+/// [calendars]
+/// NYSE = "nyse_holidays.txt"
+///
+/// [oauth2.TD]                                 # OAuth2 provider used with auth = { oauth2 = "TD" }
+/// token_url = "https://api.tdameritrade.com/v1/oauth2/token"
+/// grant = "refresh_token"                     # or client_credentials
+/// refresh_token = "[[TDREFRESHTOKEN]]"
+/// client_id = "[[TDCLIENTID]]"
+/// client_secret = "[[TDSECRET]]"              # optional, as is scope
+/// refresh_margin_secs = 60                    # renew a token expiring within the margin
+///
+/// [rate_limit.TD]                             # shared by schedules naming it or using a listed host
+/// hosts = ["api.tdameritrade.com"]
+/// requests_per_sec = 2                        # requests over the limit wait,
+/// requests_per_day = 5000                     # any of _sec, _min, _hour and _day
+///
+/// [client]                                    # shared http client, every field is optional
+/// max_connections = 0                         # 0 for no limit
+/// max_connections_per_host = 6
+/// connection_cache_size = 100
+/// idle_timeout_secs = 90
+/// tcp_keepalive_secs = 60                     # 0 to disable
+///
+/// [[consumer]]
+/// type = "stdout"
+///
+/// [[consumer]]
+/// type = "csv"
+/// path = "data.csv"
+///
+/// [[schedule]]
+/// source_name = "TD_QUOTE"
+/// url = "https://api.tdameritrade.com/v1/marketdata/quotes?symbol=[[TDQUOTESYMBOLS]]"
+/// method = "GET"                              # GET (default), POST, PUT, PATCH, DELETE or HEAD
+/// headers = { Accept = "application/json" }
+/// auth = { oauth2 = "TD" }                    # or { bearer = { token_var = "TOKEN" } }
+///                                             # or { basic = { username = "me", password_var = "PASS" } }
+///                                             # or { api_key = { query = "appid", key_var = "KEY" } },
+///                                             # header = "X-Api-Key" in place of query
+/// body = "symbol=[[TDQUOTESYMBOLS]]"          # form, or { json = '{"a": 1}' }
+///                                             # or { content_type = "text/plain", content = "..." }
+/// cron = "10 */15 9-16 * * Mon-Fri"           # or interval_secs = 90, optional offset_secs = 10
+/// timezone = "America/New_York"
+/// calendar = "NYSE"
+/// rate_limit = "TD"                           # optional, the limit listing the url host by default
+/// query = "*.{measure_name: symbol, measure_data: {mark: mark}}"   # jmespath for json responses
+/// action = "PUBLISHDATA"                      # PUBLISHDATA (default) or STOREVARIABLE
+/// lenient = true                              # convert numeric strings, leave out and log values
+///                                             # that are not a number, boolean or string
+/// labels = { exchange = "NYSE" }              # optional, added to every data point, a labels
+///                                             # object in a measure of the query takes precedence
+/// connect_timeout_secs = 10                   # limits per attempt, 0 for no limit
+/// header_timeout_secs = 30                    # except connecting, which stops after 300 secs
+/// timeout_secs = 60
+/// max_body_bytes = 10485760
+/// conditional = "skip"                        # off by default, GET with If-None-Match and a 304
+///                                             # publishes nothing, "republish" for the last data again
+/// depends_on = ["TD_AUTH"]                    # source names of schedules run before the first run
+/// requires_variables = ["TDTOKEN"]            # variables present before the first run
+///
+/// [schedule.pagination]                       # optional, results of every page are merged
+/// next_link = "links.next"                    # jmespath to the next page url,
+///                                             # or cursor = "meta.cursor" sent as param = "after",
+///                                             # or param = "page" alone counting from start = 1 by step = 1
+/// max_pages = 10
+///
+/// [schedule.flatten]                          # optional, nested measure_data becomes wind.speed
+/// separator = "."                             # between object keys, arrays are always bands[0]
+/// max_depth = 5                               # descriptor levels at most, 1 for no flattening
+///
+/// [schedule.csv]                              # in place of query for a csv response, each row a measure
+/// name_column = "symbol"
+/// value_columns = ["last", "volume"]          # every other column when left out
+/// timestamp_column = "time"                   # optional, also delimiter = ";"
+///
+/// [schedule.xml]                              # in place of query for an xml response
+/// measures = "quotes/quote"                   # path to the elements that are measures
+/// name = "@symbol"                            # child element path, or attribute after @
+/// values = ["last", "bid/@size"]              # every child element with text when left out
+/// timestamp = "@time"                         # optional
+///
+/// [schedule.regex]                            # in place of query for a text response
+/// pattern = '(?P<measure_name>\w+) (?P<last>[\d.]+)'  # named groups are values,
+///                                             # measure_name and timestamp groups are special
+/// measure_name = "SPY"                        # when the pattern has no measure_name group
+///
+/// [schedule.retry]                            # every field is optional
+/// max_attempts = 3
+/// base_delay_ms = 500
+/// max_delay_ms = 30000
+/// jitter = 0.1
+/// retry_statuses = [429, 503]
+/// ```
+#[derive(Debug)]
+pub struct Config {
+    /// shared variables filled from `[variables]`, used by every schedule
+    pub storage_var: SharedVar,
+    /// settings to pass to `configure_client` before starting schedules
+    pub client: ClientConfig,
+    pub calendars: Vec<AddCalendar>,
+    pub auth_providers: Vec<AddAuthProvider>,
+    pub rate_limits: Vec<AddRateLimit>,
+    pub consumers: Vec<ConsumerConfig>,
+    pub schedules: Vec<WebProducerSchedule>,
+}
+
+/// Consumer to start for a configuration file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ConsumerConfig {
+    Stdout,
+    Csv { path: PathBuf },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    variables: BTreeMap<String, VariableConfig>,
+    #[serde(default)]
+    calendars: BTreeMap<String, PathBuf>,
+    #[serde(default)]
+    oauth2: BTreeMap<String, OAuth2Settings>,
+    #[serde(default)]
+    rate_limit: BTreeMap<String, RateLimitSettings>,
+    client: Option<ClientSettings>,
+    #[serde(default, rename = "consumer")]
+    consumers: Vec<ConsumerConfig>,
+    #[serde(default, rename = "schedule")]
+    schedules: Vec<ScheduleConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OAuth2Settings {
+    token_url: String,
+    grant: GrantConfig,
+    refresh_token: Option<String>,
+    client_id: String,
+    client_secret: Option<String>,
+    scope: Option<String>,
+    refresh_margin_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum GrantConfig {
+    RefreshToken,
+    ClientCredentials,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RateLimitSettings {
+    #[serde(default)]
+    hosts: Vec<String>,
+    requests_per_sec: Option<u32>,
+    requests_per_min: Option<u32>,
+    requests_per_hour: Option<u32>,
+    requests_per_day: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClientSettings {
+    max_connections: Option<usize>,
+    max_connections_per_host: Option<usize>,
+    connection_cache_size: Option<usize>,
+    idle_timeout_secs: Option<u64>,
+    tcp_keepalive_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum VariableConfig {
+    Value(String),
+    Env {
+        env: String,
+        default: Option<String>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScheduleConfig {
+    source_name: String,
+    url: String,
+    method: Option<ApiRequestType>,
+    body: Option<BodyConfig>,
+    #[serde(default, deserialize_with = "ordered_pairs")]
+    headers: Vec<(String, String)>,
+    auth: Option<RequestAuth>,
+    cron: Option<String>,
+    interval_secs: Option<u64>,
+    offset_secs: Option<u64>,
+    timezone: Option<String>,
+    calendar: Option<String>,
+    rate_limit: Option<String>,
+    query: Option<String>,
+    csv: Option<CsvColumns>,
+    xml: Option<XmlFields>,
+    regex: Option<RegexCaptures>,
+    action: Option<ProducerAction>,
+    #[serde(default)]
+    lenient: bool,
+    #[serde(default)]
+    labels: Labels,
+    connect_timeout_secs: Option<u64>,
+    header_timeout_secs: Option<u64>,
+    timeout_secs: Option<u64>,
+    max_body_bytes: Option<u64>,
+    conditional: Option<ConditionalRequest>,
+    retry: Option<RetryConfig>,
+    pagination: Option<PaginationConfig>,
+    flatten: Option<FlattenConfig>,
+    #[serde(default)]
+    depends_on: Vec<String>,
+    #[serde(default)]
+    requires_variables: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum BodyConfig {
+    Form(String),
+    Json {
+        json: String,
+    },
+    Raw {
+        content_type: String,
+        content: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PaginationConfig {
+    next_link: Option<String>,
+    cursor: Option<String>,
+    param: Option<String>,
+    start: Option<u64>,
+    step: Option<u64>,
+    max_pages: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FlattenConfig {
+    separator: Option<String>,
+    max_depth: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RetryConfig {
+    max_attempts: Option<u32>,
+    base_delay_ms: Option<u64>,
+    max_delay_ms: Option<u64>,
+    jitter: Option<f64>,
+    retry_statuses: Option<Vec<u16>>,
+}
+
+impl Config {
+    /// load and validate configuration file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> DataWatchResult<Self> {
+        Config::load(path.as_ref(), None)
+    }
+
+    /// load configuration to replay fixtures without credentials, a variable whose
+    /// environment variable is not set and that has no default holds `REDACTED`
+    pub fn from_file_for_replay<P: AsRef<Path>>(path: P) -> DataWatchResult<Self> {
+        Config::load(path.as_ref(), Some(REDACTED))
+    }
+
+    fn load(path: &Path, unset: Option<&str>) -> DataWatchResult<Self> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            DataWatchError::Config(format!("{} could not be read: {}", path.display(), e))
+        })?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Config::parse_with(&text, base_dir, &|name| env::var(name).ok(), unset)
+    }
+
+    /// parse and validate configuration text, relative paths are joined to `base_dir`
+    ///
+    /// every problem found is reported, one per line
+    pub fn parse(text: &str, base_dir: &Path) -> DataWatchResult<Self> {
+        Config::parse_with(text, base_dir, &|name| env::var(name).ok(), None)
+    }
+
+    /// parse with environment variables read by `lookup`, a variable without value
+    /// or default holds `unset` if given and is an error otherwise
+    fn parse_with(
+        text: &str,
+        base_dir: &Path,
+        lookup: &dyn Fn(&str) -> Option<String>,
+        unset: Option<&str>,
+    ) -> DataWatchResult<Self> {
+        let file: ConfigFile =
+            toml::from_str(text).map_err(|e| DataWatchError::Config(e.to_string()))?;
+        let mut errors = Vec::new();
+
+        let mut variables = HashMap::new();
+        for (name, variable) in file.variables {
+            match variable {
+                VariableConfig::Value(value) => {
+                    variables.insert(name, value);
+                }
+                VariableConfig::Env { env, default } => {
+                    match lookup(&env).or(default).or_else(|| unset.map(String::from)) {
+                        Some(value) => {
+                            variables.insert(name, value);
+                        }
+                        None => errors.push(format!(
+                            "variable {}: environment variable {} is not set",
+                            name, env
+                        )),
+                    }
+                }
+            }
+        }
+        let storage_var: SharedVar = Arc::new(RwLock::new(variables));
+
+        let client = file
+            .client
+            .map(ClientSettings::into_config)
+            .unwrap_or_default();
+
+        let mut calendars = Vec::new();
+        for (name, path) in file.calendars.iter() {
+            match Calendar::from_file(name, base_dir.join(path)) {
+                Ok(calendar) => calendars.push(AddCalendar(calendar)),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+
+        let mut auth_providers = Vec::new();
+        for (name, settings) in file.oauth2.iter() {
+            let grant = match (&settings.grant, &settings.refresh_token) {
+                (GrantConfig::RefreshToken, Some(token)) => {
+                    OAuth2Grant::RefreshToken(token.clone())
+                }
+                (GrantConfig::RefreshToken, None) => {
+                    errors.push(format!(
+                        "oauth2 {}: refresh_token grant needs refresh_token",
+                        name
+                    ));
+                    continue;
+                }
+                (GrantConfig::ClientCredentials, _) => OAuth2Grant::ClientCredentials,
+            };
+            auth_providers.push(AddAuthProvider(OAuth2Config {
+                name: name.clone(),
+                token_url: settings.token_url.clone(),
+                grant,
+                client_id: settings.client_id.clone(),
+                client_secret: settings.client_secret.clone(),
+                scope: settings.scope.clone(),
+                refresh_margin: Duration::from_secs(settings.refresh_margin_secs.unwrap_or(60)),
+                storage_var: storage_var.clone(),
+            }));
+        }
+
+        let mut rate_limits = Vec::new();
+        for (name, settings) in file.rate_limit.iter() {
+            match settings.rates() {
+                Ok(rates) => rate_limits.push(AddRateLimit(RateLimitConfig {
+                    name: name.clone(),
+                    hosts: settings.hosts.clone(),
+                    rates,
+                })),
+                Err(reason) => errors.push(format!("rate_limit {}: {}", name, reason)),
+            }
+        }
+
+        let consumers = file
+            .consumers
+            .into_iter()
+            .map(|consumer| match consumer {
+                ConsumerConfig::Csv { path } => ConsumerConfig::Csv {
+                    path: base_dir.join(path),
+                },
+                consumer => consumer,
+            })
+            .collect();
+
+        let source_names: Vec<String> = file
+            .schedules
+            .iter()
+            .map(|schedule| schedule.source_name.clone())
+            .collect();
+        let mut schedules = Vec::new();
+        for (index, schedule) in file.schedules.into_iter().enumerate() {
+            let name = format!("schedule {} ({})", index + 1, schedule.source_name);
+            if let Some(calendar) = &schedule.calendar {
+                if !file.calendars.contains_key(calendar) {
+                    errors.push(format!("{}: unknown calendar {}", name, calendar));
+                }
+            }
+            if let Some(RequestAuth::OAuth2(provider)) = &schedule.auth {
+                if !file.oauth2.contains_key(provider) {
+                    errors.push(format!("{}: unknown oauth2 provider {}", name, provider));
+                }
+            }
+            if let Some(rate_limit) = &schedule.rate_limit {
+                if !file.rate_limit.contains_key(rate_limit) {
+                    errors.push(format!("{}: unknown rate_limit {}", name, rate_limit));
+                }
+            }
+            for dependency in schedule.depends_on.iter() {
+                if !source_names.contains(dependency) {
+                    errors.push(format!(
+                        "{}: depends on unknown schedule {}",
+                        name, dependency
+                    ));
+                }
+            }
+            match schedule.into_request(&storage_var) {
+                Ok(request) => schedules.push(request),
+                Err(DataWatchError::Config(reason)) => errors.push(format!("{}: {}", name, reason)),
+                Err(e) => errors.push(format!("{}: {}", name, e)),
+            }
+        }
+
+        match dependency_order(&schedules) {
+            Ok(_) => {}
+            Err(DataWatchError::Config(reason)) => errors.push(reason),
+            Err(e) => errors.push(e.to_string()),
+        }
+        if !errors.is_empty() {
+            return Err(DataWatchError::Config(errors.join("\n")));
+        }
+        Ok(Config {
+            storage_var,
+            client,
+            calendars,
+            auth_providers,
+            rate_limits,
+            consumers,
+            schedules,
+        })
+    }
+}
+
+impl RateLimitSettings {
+    fn rates(&self) -> Result<Vec<Rate>, &'static str> {
+        let rates: Vec<Rate> = [
+            (self.requests_per_sec, 1),
+            (self.requests_per_min, 60),
+            (self.requests_per_hour, 60 * 60),
+            (self.requests_per_day, 24 * 60 * 60),
+        ]
+        .iter()
+        .filter_map(|(requests, secs)| {
+            requests.map(|requests| Rate {
+                requests,
+                per: Duration::from_secs(*secs),
+            })
+        })
+        .collect();
+        if rates.is_empty() {
+            Err("needs requests_per_sec, _min, _hour or _day")
+        } else if rates.iter().any(|rate| rate.requests == 0) {
+            Err("requests must be above 0")
+        } else {
+            Ok(rates)
+        }
+    }
+}
+
+impl ClientSettings {
+    fn into_config(self) -> ClientConfig {
+        let default = ClientConfig::default();
+        ClientConfig {
+            max_connections: self.max_connections.unwrap_or(default.max_connections),
+            max_connections_per_host: self
+                .max_connections_per_host
+                .unwrap_or(default.max_connections_per_host),
+            connection_cache_size: self
+                .connection_cache_size
+                .unwrap_or(default.connection_cache_size),
+            idle_timeout: self
+                .idle_timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(default.idle_timeout),
+            tcp_keepalive: seconds_limit(self.tcp_keepalive_secs, default.tcp_keepalive),
+        }
+    }
+}
+
+impl ScheduleConfig {
+    fn into_request(self, storage_var: &SharedVar) -> DataWatchResult<WebProducerSchedule> {
+        let schedule = match (self.cron, self.interval_secs, self.offset_secs) {
+            (Some(cron), None, None) => ScheduleType::Cron(cron),
+            (None, Some(every), offset) => ScheduleType::Interval {
+                every: Duration::from_secs(every),
+                offset: offset.map(Duration::from_secs),
+            },
+            (Some(_), _, _) => {
+                return Err(invalid("use either cron or interval_secs/offset_secs"));
+            }
+            (None, None, _) => return Err(invalid("missing cron or interval_secs")),
+        };
+        let retry = self.retry.map(|retry| {
+            let default = RetryPolicy::default();
+            RetryPolicy {
+                max_attempts: retry.max_attempts.unwrap_or(default.max_attempts),
+                base_delay: retry
+                    .base_delay_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(default.base_delay),
+                max_delay: retry
+                    .max_delay_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(default.max_delay),
+                jitter: retry.jitter.unwrap_or(default.jitter),
+                retry_statuses: retry.retry_statuses.unwrap_or(default.retry_statuses),
+            }
+        });
+        let pagination = match self.pagination {
+            Some(pagination) => Some(pagination.into_pagination()?),
+            None => None,
+        };
+        let format = match (self.query.is_some(), self.csv, self.xml, self.regex) {
+            (false, Some(columns), None, None) => ResponseFormat::Csv(columns),
+            (false, None, Some(fields), None) => ResponseFormat::Xml(fields),
+            (false, None, None, Some(captures)) => ResponseFormat::Regex(captures),
+            (true, None, None, None) => ResponseFormat::Json,
+            _ => return Err(invalid("schedule needs one of query, csv, xml or regex")),
+        };
+        let default_flatten = Flatten::default();
+        let flatten = match self.flatten {
+            Some(FlattenConfig {
+                max_depth: Some(0), ..
+            }) => return Err(invalid("flatten max_depth must be above 0")),
+            Some(flatten) => Flatten {
+                separator: flatten.separator.unwrap_or(default_flatten.separator),
+                max_depth: flatten.max_depth.unwrap_or(default_flatten.max_depth),
+            },
+            None => default_flatten,
+        };
+        let default_limits = RequestLimits::default();
+        let limits = RequestLimits {
+            connect_timeout: seconds_limit(
+                self.connect_timeout_secs,
+                default_limits.connect_timeout,
+            ),
+            header_timeout: seconds_limit(self.header_timeout_secs, default_limits.header_timeout),
+            total_timeout: seconds_limit(self.timeout_secs, default_limits.total_timeout),
+            max_body_size: match self.max_body_bytes {
+                Some(0) => None,
+                Some(bytes) => Some(bytes),
+                None => default_limits.max_body_size,
+            },
+        };
+        let depends_on = self
+            .depends_on
+            .into_iter()
+            .map(Dependency::Schedule)
+            .chain(
+                self.requires_variables
+                    .into_iter()
+                    .map(Dependency::Variable),
+            )
+            .collect();
+        let request = WebProducerSchedule {
+            source_name: self.source_name,
+            api_url: self.url,
+            request_type: self.method.unwrap_or(ApiRequestType::GET),
+            body: self.body.map(|body| match body {
+                BodyConfig::Form(text) => RequestBody::Form(text),
+                BodyConfig::Json { json } => RequestBody::Json(json),
+                BodyConfig::Raw {
+                    content_type,
+                    content,
+                } => RequestBody::Raw {
+                    content_type,
+                    content,
+                },
+            }),
+            headers: self.headers,
+            auth: self.auth,
+            rate_limit: self.rate_limit,
+            schedule,
+            calendar: self.calendar,
+            timezone: self.timezone,
+            jmespatch_query: self.query.unwrap_or_default(),
+            format,
+            lenient: self.lenient,
+            flatten,
+            labels: self.labels,
+            retry,
+            limits,
+            conditional: self.conditional.unwrap_or_default(),
+            pagination,
+            depends_on,
+            storage_var: storage_var.clone(),
+            response_action: self.action.unwrap_or(ProducerAction::PUBLISHDATA),
+        };
+        // checks query, schedule and timezone the same way the Scheduler will
+        WebProducer::new(request.clone())?;
+        Ok(request)
+    }
+}
+
+impl PaginationConfig {
+    fn into_pagination(self) -> DataWatchResult<Pagination> {
+        let next = match (self.next_link, self.cursor, self.param) {
+            (Some(link), None, None) => NextPage::Link(link),
+            (None, Some(query), Some(param)) => NextPage::Cursor { query, param },
+            (None, None, Some(param)) => NextPage::Offset {
+                param,
+                start: self.start.unwrap_or(1),
+                step: self.step.unwrap_or(1),
+            },
+            _ => {
+                return Err(invalid(
+                    "pagination needs next_link, cursor with param, or param alone",
+                ))
+            }
+        };
+        match self.max_pages.unwrap_or(10) {
+            0 => Err(invalid("pagination max_pages must be above 0")),
+            max_pages => Ok(Pagination { next, max_pages }),
+        }
+    }
+}
+
+/// limit in seconds from configuration, 0 for no limit
+fn seconds_limit(secs: Option<u64>, default: Option<Duration>) -> Option<Duration> {
+    match secs {
+        Some(0) => None,
+        Some(secs) => Some(Duration::from_secs(secs)),
+        None => default,
+    }
+}
+
+fn invalid(reason: &str) -> DataWatchError {
+    DataWatchError::Config(reason.to_owned())
+}
+
+/// table of strings kept in the order written, such as headers
+fn ordered_pairs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(String, String)>, D::Error> {
+    struct PairsVisitor;
+
+    impl<'de> Visitor<'de> for PairsVisitor {
+        type Value = Vec<(String, String)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a table of strings")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut pairs = Vec::new();
+            while let Some(pair) = map.next_entry()? {
+                pairs.push(pair);
+            }
+            Ok(pairs)
+        }
+    }
+
+    deserializer.deserialize_map(PairsVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::producer::ApiKeyLocation;
+
+    const CONFIG: &str = r#"
+        [variables]
+        SYMBOLS = "BTC-USD"
+        TOKEN = { env = "DATA_WATCH_TEST_UNSET_TOKEN", default = "secret" }
+
+        [oauth2.BROKER]
+        token_url = "https://example.com/oauth2/token"
+        grant = "refresh_token"
+        refresh_token = "[[TOKEN]]"
+        client_id = "data-watch"
+
+        [rate_limit.BROKER]
+        hosts = ["example.com"]
+        requests_per_sec = 2
+        requests_per_day = 1000
+
+        [client]
+        max_connections_per_host = 2
+        tcp_keepalive_secs = 0
+
+        [[consumer]]
+        type = "stdout"
+
+        [[consumer]]
+        type = "csv"
+        path = "data.csv"
+
+        [[schedule]]
+        source_name = "COINBASE_PRO"
+        url = "https://api.pro.coinbase.com/products/[[SYMBOLS]]/ticker"
+        headers = { Authorization = "Bearer [[TOKEN]]", Accept = "application/json" }
+        cron = "0 */1 * * * *"
+        depends_on = ["AUTH"]
+        requires_variables = ["TOKEN"]
+        query = "merge({ measure_data: {mark: to_number(price)} }, { measure_name: `\"BTC-USD\"`})"
+
+        [[schedule]]
+        source_name = "AUTH"
+        url = "https://example.com/token"
+        method = "POST"
+        body = "grant_type=client_credentials"
+        interval_secs = 1700
+        offset_secs = 5
+        query = "{ TOKEN: access_token }"
+        action = "STOREVARIABLE"
+        connect_timeout_secs = 2
+        header_timeout_secs = 5
+        timeout_secs = 0
+
+        [schedule.retry]
+        max_attempts = 5
+        retry_statuses = [503]
+
+        [schedule.pagination]
+        cursor = "meta.cursor"
+        param = "after"
+
+        [[schedule]]
+        source_name = "SEARCH"
+        url = "https://example.com/search"
+        method = "PATCH"
+        auth = { oauth2 = "BROKER" }
+        rate_limit = "BROKER"
+        body = { json = '{"symbol": "[[SYMBOLS]]"}' }
+        conditional = "skip"
+        interval_secs = 60
+        query = "@"
+        lenient = true
+        labels = { exchange = "NYSE", currency = "USD" }
+
+        [schedule.flatten]
+        separator = "_"
+    "#;
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(CONFIG, Path::new("config")).unwrap();
+        assert_eq!(
+            config.storage_var.read().unwrap().get("TOKEN").unwrap(),
+            "secret"
+        );
+        assert_eq!(
+            config.client,
+            ClientConfig {
+                max_connections_per_host: 2,
+                tcp_keepalive: None,
+                ..ClientConfig::default()
+            }
+        );
+        assert_eq!(
+            config.consumers,
+            vec![
+                ConsumerConfig::Stdout,
+                ConsumerConfig::Csv {
+                    path: PathBuf::from("config/data.csv")
+                }
+            ]
+        );
+        assert_eq!(config.schedules.len(), 3);
+        let quote = &config.schedules[0];
+        assert!(matches!(quote.request_type, ApiRequestType::GET));
+        assert!(matches!(quote.response_action, ProducerAction::PUBLISHDATA));
+        assert_eq!(
+            quote.headers,
+            vec![
+                (
+                    String::from("Authorization"),
+                    String::from("Bearer [[TOKEN]]")
+                ),
+                (String::from("Accept"), String::from("application/json")),
+            ]
+        );
+        assert_eq!(
+            quote.depends_on,
+            vec![
+                Dependency::Schedule(String::from("AUTH")),
+                Dependency::Variable(String::from("TOKEN"))
+            ]
+        );
+        let auth = &config.schedules[1];
+        assert!(matches!(auth.request_type, ApiRequestType::POST));
+        assert!(matches!(auth.body, Some(RequestBody::Form(_))));
+        assert_eq!(
+            auth.limits,
+            RequestLimits {
+                connect_timeout: Some(Duration::from_secs(2)),
+                header_timeout: Some(Duration::from_secs(5)),
+                total_timeout: None,
+                ..RequestLimits::default()
+            }
+        );
+        assert!(matches!(
+            auth.schedule,
+            ScheduleType::Interval {
+                offset: Some(_),
+                ..
+            }
+        ));
+        let retry = auth.retry.as_ref().unwrap();
+        assert_eq!(retry.max_attempts, 5);
+        assert_eq!(retry.retry_statuses, vec![503]);
+        assert_eq!(retry.base_delay, RetryPolicy::default().base_delay);
+        assert_eq!(
+            auth.pagination,
+            Some(Pagination {
+                next: NextPage::Cursor {
+                    query: String::from("meta.cursor"),
+                    param: String::from("after")
+                },
+                max_pages: 10
+            })
+        );
+        assert_eq!(config.auth_providers.len(), 1);
+        let provider = &config.auth_providers[0].0;
+        assert_eq!(provider.name, "BROKER");
+        assert_eq!(
+            provider.grant,
+            OAuth2Grant::RefreshToken(String::from("[[TOKEN]]"))
+        );
+        assert_eq!(provider.refresh_margin, Duration::from_secs(60));
+        let search = &config.schedules[2];
+        assert_eq!(
+            search.auth,
+            Some(RequestAuth::OAuth2(String::from("BROKER")))
+        );
+        assert_eq!(search.rate_limit, Some(String::from("BROKER")));
+        assert_eq!(search.conditional, ConditionalRequest::Skip);
+        assert_eq!(quote.conditional, ConditionalRequest::Off);
+        assert!(search.lenient);
+        assert!(!quote.lenient);
+        assert_eq!(
+            search.flatten,
+            Flatten {
+                separator: String::from("_"),
+                max_depth: 5
+            }
+        );
+        assert_eq!(quote.flatten, Flatten::default());
+        assert_eq!(
+            search.labels.iter().collect::<Vec<_>>(),
+            vec![
+                (&String::from("currency"), &String::from("USD")),
+                (&String::from("exchange"), &String::from("NYSE"))
+            ]
+        );
+        assert!(quote.labels.is_empty());
+        assert_eq!(config.rate_limits.len(), 1);
+        assert_eq!(
+            config.rate_limits[0].0.rates,
+            vec![
+                Rate {
+                    requests: 2,
+                    per: Duration::from_secs(1)
+                },
+                Rate {
+                    requests: 1000,
+                    per: Duration::from_secs(24 * 60 * 60)
+                }
+            ]
+        );
+        assert!(matches!(search.request_type, ApiRequestType::PATCH));
+        assert!(
+            matches!(&search.body, Some(RequestBody::Json(json)) if json == r#"{"symbol": "[[SYMBOLS]]"}"#)
+        );
+    }
+
+    #[test]
+    fn headers_kept_in_written_order() {
+        let text = r#"
+            [[schedule]]
+            source_name = "QUOTE"
+            url = "https://example.com"
+            interval_secs = 60
+            query = "@"
+
+            [schedule.headers]
+            X-Trace = "1"
+            User-Agent = "data-watch"
+            Accept = "application/json"
+            Authorization = "Bearer [[TOKEN]]"
+        "#;
+        let config = Config::parse(text, Path::new("")).unwrap();
+        let names: Vec<&str> = config.schedules[0]
+            .headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["X-Trace", "User-Agent", "Accept", "Authorization"]);
+    }
+
+    #[test]
+    fn parse_static_auth() {
+        let text = r#"
+            [[schedule]]
+            source_name = "BASIC"
+            url = "https://example.com"
+            interval_secs = 60
+            auth = { basic = { username = "me", password_var = "PASS" } }
+            query = "@"
+
+            [[schedule]]
+            source_name = "BEARER"
+            url = "https://example.com"
+            interval_secs = 60
+            auth = { bearer = { token_var = "TOKEN" } }
+            query = "@"
+
+            [[schedule]]
+            source_name = "API_KEY"
+            url = "https://example.com"
+            interval_secs = 60
+            auth = { api_key = { header = "X-Api-Key", key_var = "KEY" } }
+            query = "@"
+        "#;
+        let config = Config::parse(text, Path::new("")).unwrap();
+        let auth: Vec<_> = config
+            .schedules
+            .into_iter()
+            .map(|schedule| schedule.auth.unwrap())
+            .collect();
+        assert_eq!(
+            auth,
+            vec![
+                RequestAuth::Basic {
+                    username: String::from("me"),
+                    password_var: String::from("PASS"),
+                },
+                RequestAuth::Bearer {
+                    token_var: String::from("TOKEN"),
+                },
+                RequestAuth::ApiKey {
+                    location: ApiKeyLocation::Header(String::from("X-Api-Key")),
+                    key_var: String::from("KEY"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_response_formats() {
+        let text = r#"
+            [[schedule]]
+            source_name = "CSV"
+            url = "https://example.com/quotes.csv"
+            interval_secs = 60
+
+            [schedule.csv]
+            name_column = "symbol"
+            delimiter = ";"
+
+            [[schedule]]
+            source_name = "BOTH"
+            url = "https://example.com"
+            interval_secs = 60
+            query = "@"
+
+            [schedule.regex]
+            pattern = '(?P<last>\d+)'
+        "#;
+        let error = Config::parse(text, Path::new("")).unwrap_err().to_string();
+        assert!(error.contains("schedule 2 (BOTH): schedule needs one of query, csv, xml or regex"));
+
+        let text = text.replace("query = \"@\"", "");
+        let error = Config::parse(&text, Path::new("")).unwrap_err().to_string();
+        assert!(
+            error.contains("schedule 2 (BOTH): regex needs a measure_name group or measure_name")
+        );
+
+        let text = text.replace("pattern", "measure_name = \"SPY\"\n            pattern");
+        let config = Config::parse(&text, Path::new("")).unwrap();
+        assert_eq!(
+            config.schedules[0].format,
+            ResponseFormat::Csv(CsvColumns {
+                name_column: String::from("symbol"),
+                value_columns: Vec::new(),
+                timestamp_column: None,
+                delimiter: ';',
+            })
+        );
+        assert_eq!(
+            config.schedules[1].format,
+            ResponseFormat::Regex(RegexCaptures {
+                pattern: String::from(r"(?P<last>\d+)"),
+                measure_name: Some(String::from("SPY")),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_example_configs() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/config");
+        let lookup = |_: &str| Some(String::from("test"));
+        for file in &["weather.toml", "crypto.toml", "stockquote.toml"] {
+            let text = fs::read_to_string(dir.join(file)).unwrap();
+            if let Err(e) = Config::parse_with(&text, &dir, &lookup, None) {
+                panic!("{}: {}", file, e);
+            }
+        }
+    }
+
+    #[test]
+    fn replay_without_credentials() {
+        let text = r#"
+            [variables]
+            KEY = { env = "DATA_WATCH_TEST_UNSET_KEY" }
+            REGION = { env = "DATA_WATCH_TEST_UNSET_REGION", default = "us" }
+        "#;
+        assert!(Config::parse_with(text, Path::new(""), &|_| None, None).is_err());
+        let config = Config::parse_with(text, Path::new(""), &|_| None, Some(REDACTED)).unwrap();
+        let variables = config.storage_var.read().unwrap();
+        assert_eq!(variables["KEY"], "REDACTED");
+        assert_eq!(variables["REGION"], "us");
+    }
+
+    #[test]
+    fn report_every_invalid_field() {
+        let text = r#"
+            [variables]
+            KEY = { env = "DATA_WATCH_TEST_UNSET_KEY" }
+
+            [[schedule]]
+            source_name = "BAD_CRON"
+            url = "https://example.com"
+            cron = "every minute"
+            query = "@"
+
+            [[schedule]]
+            source_name = "BAD_QUERY"
+            url = "https://example.com"
+            interval_secs = 60
+            calendar = "MISSING"
+            depends_on = ["NOWHERE"]
+            auth = { oauth2 = "NOBODY" }
+            rate_limit = "UNLIMITED"
+            query = "[["
+
+            [rate_limit.UNLIMITED]
+            hosts = ["example.com"]
+
+            [[schedule]]
+            source_name = "BAD_PAGES"
+            url = "https://example.com"
+            interval_secs = 60
+            query = "@"
+
+            [schedule.pagination]
+            cursor = "next"
+
+            [[schedule]]
+            source_name = "BAD_INTERVAL"
+            url = "https://example.com"
+            interval_secs = 0
+            query = "@"
+
+            [oauth2.NO_REFRESH]
+            token_url = "https://example.com/token"
+            grant = "refresh_token"
+            client_id = "data-watch"
+        "#;
+        let error = Config::parse(text, Path::new("")).unwrap_err().to_string();
+        assert!(error.contains("DATA_WATCH_TEST_UNSET_KEY"));
+        assert!(error.contains("schedule 1 (BAD_CRON)"));
+        assert!(error.contains("schedule 2 (BAD_QUERY): unknown calendar MISSING"));
+        assert!(error.contains("schedule 2 (BAD_QUERY): jmespath"));
+        assert!(error.contains("schedule 2 (BAD_QUERY): depends on unknown schedule NOWHERE"));
+        assert!(error.contains("schedule 2 (BAD_QUERY): unknown oauth2 provider NOBODY"));
+        assert!(error.contains("schedule 3 (BAD_PAGES): pagination needs next_link"));
+        assert!(error.contains("schedule 4 (BAD_INTERVAL): invalid schedule: invalid interval"));
+        assert!(error.contains("oauth2 NO_REFRESH: refresh_token grant needs refresh_token"));
+        assert!(error.contains("rate_limit UNLIMITED: needs requests_per_sec"));
+    }
+
+    #[test]
+    fn reject_dependency_cycle() {
+        let text = r#"
+            [[schedule]]
+            source_name = "AUTH"
+            url = "https://example.com/auth"
+            interval_secs = 60
+            query = "@"
+            depends_on = ["QUOTE"]
+
+            [[schedule]]
+            source_name = "QUOTE"
+            url = "https://example.com/quote"
+            interval_secs = 60
+            query = "@"
+            depends_on = ["AUTH"]
+        "#;
+        let error = Config::parse(text, Path::new("")).unwrap_err().to_string();
+        assert!(error.contains("schedules depend on each other: AUTH -> QUOTE -> AUTH"));
+    }
+
+    #[test]
+    fn reject_unknown_fields() {
+        let text = r#"
+            [[schedule]]
+            source_name = "TYPO"
+            url = "https://example.com"
+            cron = "0 * * * * *"
+            qurey = "@"
+        "#;
+        assert!(matches!(
+            Config::parse(text, Path::new("")),
+            Err(DataWatchError::Config(_))
+        ));
+        let text = r#"
+            [[consumer]]
+            type = "csv"
+            file = "data.csv"
+        "#;
+        assert!(Config::parse(text, Path::new("")).is_err());
+    }
+}
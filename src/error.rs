@@ -0,0 +1,76 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Errors that can happen while building, requesting or parsing a producer request
+///
+/// Errors are cloneable so they can be carried inside a `ProducerError` message
+/// and published on the broker
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataWatchError {
+    /// request could not be sent or the response could not be read
+    Request(String),
+    /// server responded with a status that is not a success
+    Status(u16),
+    /// no connection to the host within the connect timeout
+    ConnectTimeout(Duration),
+    /// no response headers within the header timeout
+    HeaderTimeout(Duration),
+    /// request and response body not complete within the total timeout
+    Timeout(Duration),
+    /// response body is larger than the limit in bytes
+    ResponseTooLarge(u64),
+    /// credentials for a request could not be obtained
+    Auth(String),
+    /// response body could not be parsed as json
+    Json(String),
+    /// jmespath expression failed to compile or to run
+    Query(String),
+    /// parsed result does not match the expected measure or variable format
+    Format(String),
+    /// schedule definition could not be parsed
+    Schedule(String),
+    /// configuration file could not be read or is not valid
+    Config(String),
+}
+
+/// Result returned by data-watch functions that can fail
+pub type DataWatchResult<T> = Result<T, DataWatchError>;
+
+impl fmt::Display for DataWatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataWatchError::Request(e) => write!(f, "request failed: {}", e),
+            DataWatchError::Status(status) => write!(f, "request returned status {}", status),
+            DataWatchError::ConnectTimeout(limit) => {
+                write!(f, "could not connect within {:?}", limit)
+            }
+            DataWatchError::HeaderTimeout(limit) => {
+                write!(f, "no response within {:?}", limit)
+            }
+            DataWatchError::Timeout(limit) => write!(f, "request not complete within {:?}", limit),
+            DataWatchError::ResponseTooLarge(limit) => {
+                write!(f, "response body larger than {} bytes", limit)
+            }
+            DataWatchError::Auth(e) => write!(f, "authentication failed: {}", e),
+            DataWatchError::Json(e) => write!(f, "invalid json response: {}", e),
+            DataWatchError::Query(e) => write!(f, "jmespath query failed: {}", e),
+            DataWatchError::Format(e) => write!(f, "unexpected data format: {}", e),
+            DataWatchError::Schedule(e) => write!(f, "invalid schedule: {}", e),
+            DataWatchError::Config(e) => write!(f, "invalid configuration: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DataWatchError {}
+
+impl From<surf::Error> for DataWatchError {
+    fn from(error: surf::Error) -> Self {
+        DataWatchError::Request(error.to_string())
+    }
+}
+
+impl From<jmespatch::JmespathError> for DataWatchError {
+    fn from(error: jmespatch::JmespathError) -> Self {
+        DataWatchError::Query(error.to_string())
+    }
+}
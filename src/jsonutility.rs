@@ -1,5 +1,11 @@
+use crate::actors::messages::MeasureValue;
+use crate::error::{DataWatchError, DataWatchResult};
+use crate::{DataPoint, DataSource, Labels};
+use chrono::DateTime;
+use jmespatch::Variable;
 use log::debug;
-use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
 
 /// Parse JSON to HashMap of DataResponse format using a jmespath expression
 ///
@@ -14,72 +20,340 @@ use std::collections::HashMap;
 /// OR Single measure in one query (includes multiple measure types)
 /// { measure_name: "", measure_data: {measure_desc1: measure_value1, measure_desc2: measure_value2} }
 ///
+/// A measure can also carry the time it was taken, otherwise publish time is used
+/// { measure_name: "", measure_data: {...}, timestamp: 1620000000 }
 ///
-/// Return should be Hashmap<String, Vec<(String, f64)>
-/// <measure_name, Vec<measure_desc, measure_value)>>
+/// and labels naming extra dimensions, values are kept as text
+/// { measure_name: "", measure_data: {...}, labels: {exchange: "NYSE", currency: "USD"} }
+///
+/// Objects and arrays in measure_data are flattened, see `Flatten`
+/// { measure_name: "", measure_data: {wind: {speed: 3.1}, bands: [{value: 1}]} }
+/// gives the descriptors wind.speed and bands[0].value
+///
+/// Return should be Hashmap<String, Vec<DataPoint>>
+/// <measure_name, Vec<DataPoint { desc, value, timestamp, labels }>>,
+/// measures with the same name are kept together
+///
+/// measure values can be numbers, booleans or strings, see `parse_timestamp` for timestamps
+///
+/// Returns an error if the response is not json, the query fails or the result
+/// does not follow the format above
 ///
 pub fn parse_json_data(
     expression: &jmespatch::Expression<'static>,
     json_response: &str,
-) -> HashMap<String, Vec<(String, f64)>> {
-    let parsed_json = jmespatch::Variable::from_json(json_response).unwrap();
-    let result = expression.search(parsed_json).unwrap();
-    let mut out = HashMap::new();
-    debug!("Parsed result: {:?}", result);
+    flatten: &Flatten,
+) -> DataWatchResult<DataSource> {
+    parse_data(expression, json_response, flatten, None)
+}
+
+/// Parse JSON as `parse_json_data`, converting numeric strings to numbers and leaving out
+/// values that are null or nested too deep and timestamps that cannot be read
+///
+/// Returns the data with a report of every field left out,
+/// errors are only for a response that does not follow the measure format
+pub fn parse_json_data_lenient(
+    expression: &jmespatch::Expression<'static>,
+    json_response: &str,
+    flatten: &Flatten,
+) -> DataWatchResult<(DataSource, Vec<DroppedField>)> {
+    let mut dropped = Vec::new();
+    let data = parse_data(expression, json_response, flatten, Some(&mut dropped))?;
+    Ok((data, dropped))
+}
+
+/// How objects and arrays in measure_data become descriptors
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flatten {
+    /// put between the keys of nested objects, array items are always `[index]`
+    pub separator: String,
+    /// levels of descriptor at most, 1 for a flat measure_data,
+    /// values nested deeper are an error or left out when lenient
+    pub max_depth: usize,
+}
+
+impl Default for Flatten {
+    fn default() -> Self {
+        Flatten {
+            separator: String::from("."),
+            max_depth: 5,
+        }
+    }
+}
+
+impl Flatten {
+    /// values under `value` paired with their descriptors starting from `desc`
+    fn collect<'a>(
+        &self,
+        desc: String,
+        value: &'a Variable,
+        depth: usize,
+        out: &mut Vec<(String, &'a Variable)>,
+    ) {
+        match value {
+            Variable::Object(entries) if depth < self.max_depth => {
+                for (key, value) in entries {
+                    let desc = format!("{}{}{}", desc, self.separator, key);
+                    self.collect(desc, value, depth + 1, out);
+                }
+            }
+            Variable::Array(items) if depth < self.max_depth => {
+                for (index, value) in items.iter().enumerate() {
+                    self.collect(format!("{}[{}]", desc, index), value, depth + 1, out);
+                }
+            }
+            _ => out.push((desc, value)),
+        }
+    }
+}
+
+/// Field of a measure left out by `parse_json_data_lenient`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroppedField {
+    pub measure_name: String,
+    /// measure_desc of the value, `timestamp` or `labels`
+    pub field: String,
+    pub reason: String,
+}
+
+impl fmt::Display for DroppedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{} {}", self.measure_name, self.field, self.reason)
+    }
+}
+
+/// strict when `dropped` is None, otherwise lenient and left out fields are added to `dropped`
+fn parse_data(
+    expression: &jmespatch::Expression<'static>,
+    json_response: &str,
+    flatten: &Flatten,
+    mut dropped: Option<&mut Vec<DroppedField>>,
+) -> DataWatchResult<DataSource> {
+    let result = search_json(expression, json_response)?;
+    let mut out = DataSource::new();
     // decide if array or object (ie multiple measures or one measure with multiple data descriptions)
     if result.is_object() {
-        let parsed = parse_one_measure(&result);
-        out.insert(parsed.0, parsed.1);
-    } else if result.is_array() {
-        for each_result in result.as_array().unwrap() {
-            let parsed = parse_one_measure(&each_result);
-            out.insert(parsed.0, parsed.1);
+        let parsed = parse_one_measure(&result, flatten, dropped)?;
+        out.entry(parsed.0).or_default().extend(parsed.1);
+    } else if let Some(results) = result.as_array() {
+        for each_result in results {
+            let parsed = parse_one_measure(each_result, flatten, dropped.as_deref_mut())?;
+            out.entry(parsed.0).or_default().extend(parsed.1);
         }
+    } else {
+        return Err(DataWatchError::Format(format!(
+            "expected an object or array of measures, found {}",
+            result
+        )));
     }
-    out
+    Ok(out)
 }
 
-fn parse_one_measure(result: &jmespatch::Variable) -> (String, Vec<(String, f64)>) {
-    let measure_name = result
+fn parse_one_measure(
+    result: &jmespatch::Variable,
+    flatten: &Flatten,
+    mut dropped: Option<&mut Vec<DroppedField>>,
+) -> DataWatchResult<(String, Vec<DataPoint>)> {
+    let measure = result
         .as_object()
-        .unwrap()
+        .ok_or_else(|| DataWatchError::Format(format!("measure is not an object: {}", result)))?;
+    let measure_name = measure
         .get("measure_name")
-        .unwrap()
-        .as_string()
-        .unwrap()
+        .and_then(|name| name.as_string())
+        .ok_or_else(|| DataWatchError::Format(String::from("missing string field measure_name")))?
         .to_owned();
-    let mut data_points = Vec::new();
-    for entry in result
-        .as_object()
-        .unwrap()
+    let measure_data = measure
         .get("measure_data")
-        .unwrap()
-        .as_object()
-        .unwrap()
-    {
-        data_points.push((entry.0.to_owned(), entry.1.as_number().unwrap()))
+        .and_then(|data| data.as_object())
+        .ok_or_else(|| {
+            DataWatchError::Format(format!(
+                "missing object field measure_data for {}",
+                measure_name
+            ))
+        })?;
+    let lenient = dropped.is_some();
+    let dropped_field = |field: &str, reason: String| DroppedField {
+        measure_name: measure_name.clone(),
+        field: field.to_owned(),
+        reason,
+    };
+    let mut timestamp = None;
+    if let Some(value) = measure.get("timestamp").filter(|value| !value.is_null()) {
+        timestamp = parse_timestamp(value);
+        if timestamp.is_none() {
+            let reason = format!("is not epoch seconds, milliseconds or RFC3339: {}", value);
+            leave_out(dropped.as_deref_mut(), dropped_field("timestamp", reason))?;
+        }
+    }
+    let mut labels = Labels::new();
+    if let Some(value) = measure.get("labels").filter(|value| !value.is_null()) {
+        match value.as_object() {
+            Some(entries) => {
+                for (name, value) in entries {
+                    match label_text(value) {
+                        Some(text) => {
+                            labels.insert(name.to_owned(), text);
+                        }
+                        None => {
+                            let reason = format!("is not a number, boolean or string: {}", value);
+                            let field = format!("labels.{}", name);
+                            leave_out(dropped.as_deref_mut(), dropped_field(&field, reason))?;
+                        }
+                    }
+                }
+            }
+            None => {
+                let reason = format!("is not an object: {}", value);
+                leave_out(dropped.as_deref_mut(), dropped_field("labels", reason))?;
+            }
+        }
+    }
+    let mut data_points = Vec::new();
+    let mut entries = Vec::new();
+    for (key, value) in measure_data {
+        flatten.collect(key.to_owned(), value, 1, &mut entries);
+    }
+    for (desc, raw) in entries {
+        let value = match measure_value(raw) {
+            Some(MeasureValue::Text(text)) if lenient => {
+                numeric_text(&text).unwrap_or(MeasureValue::Text(text))
+            }
+            Some(value) => value,
+            None => {
+                let reason = if raw.is_object() || raw.is_array() {
+                    format!("is nested more than {} levels deep", flatten.max_depth)
+                } else {
+                    format!("is not a number, boolean or string: {}", raw)
+                };
+                leave_out(dropped.as_deref_mut(), dropped_field(&desc, reason))?;
+                continue;
+            }
+        };
+        data_points.push(DataPoint {
+            desc,
+            value,
+            timestamp,
+            labels: labels.clone(),
+        })
+    }
+    Ok((measure_name, data_points))
+}
+
+/// add `field` to `dropped` when lenient, otherwise fail with it
+pub(crate) fn leave_out(
+    dropped: Option<&mut Vec<DroppedField>>,
+    field: DroppedField,
+) -> DataWatchResult<()> {
+    match dropped {
+        Some(dropped) => {
+            dropped.push(field);
+            Ok(())
+        }
+        None => Err(DataWatchError::Format(field.to_string())),
+    }
+}
+
+/// number in a string such as `"43000.12"`, integers kept exact
+pub(crate) fn numeric_text(text: &str) -> Option<MeasureValue> {
+    let text = text.trim();
+    if let Ok(value) = text.parse::<i64>() {
+        return Some(MeasureValue::Integer(value));
+    }
+    text.parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .map(MeasureValue::Float)
+}
+
+/// whole numbers within i64 stay integers, None for null, arrays and objects
+fn measure_value(value: &Variable) -> Option<MeasureValue> {
+    match value {
+        Variable::Number(number) => number
+            .as_i64()
+            .map(MeasureValue::Integer)
+            .or_else(|| number.as_f64().map(MeasureValue::Float)),
+        Variable::Bool(value) => Some(MeasureValue::Bool(*value)),
+        Variable::String(text) => Some(MeasureValue::Text(text.to_owned())),
+        _ => None,
+    }
+}
+
+/// label value as text, None for null, arrays and objects
+fn label_text(value: &Variable) -> Option<String> {
+    match value {
+        Variable::String(text) => Some(text.to_owned()),
+        Variable::Number(_) | Variable::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// epoch seconds from a number or numeric string in epoch seconds or milliseconds,
+/// or an RFC3339 string
+///
+/// numbers from 10^11 on are taken as milliseconds, as seconds they would be past the year 5000
+pub fn parse_timestamp(value: &Variable) -> Option<u64> {
+    match value {
+        Variable::Number(number) => epoch_seconds(number.as_f64()?),
+        Variable::String(text) => parse_timestamp_text(text),
+        _ => None,
+    }
+}
+
+/// epoch seconds from text in epoch seconds or milliseconds or RFC3339, see `parse_timestamp`
+pub fn parse_timestamp_text(text: &str) -> Option<u64> {
+    match text.trim().parse::<f64>() {
+        Ok(epoch) => epoch_seconds(epoch),
+        Err(_) => DateTime::parse_from_rfc3339(text.trim())
+            .ok()
+            .and_then(|time| u64::try_from(time.timestamp()).ok()),
+    }
+}
+
+fn epoch_seconds(epoch: f64) -> Option<u64> {
+    const MILLISECONDS_FROM: f64 = 1e11;
+    if !epoch.is_finite() || epoch < 0.0 {
+        None
+    } else if epoch >= MILLISECONDS_FROM {
+        Some((epoch / 1000.0) as u64)
+    } else {
+        Some(epoch as u64)
     }
-    (measure_name, data_points)
 }
 
 pub fn parse_json_pair(
     expression: &jmespatch::Expression<'static>,
     json_response: &str,
-) -> Vec<(String, String)> {
-    let parsed_json = jmespatch::Variable::from_json(json_response).unwrap();
-    let result = expression.search(parsed_json).unwrap();
-    debug!("Parsed result: {:?}", result);
+) -> DataWatchResult<Vec<(String, String)>> {
+    let result = search_json(expression, json_response)?;
+    let pairs = result.as_object().ok_or_else(|| {
+        DataWatchError::Format(format!("expected an object of variables, found {}", result))
+    })?;
     let mut out = Vec::new();
-    for entry in result.as_object().unwrap() {
-        out.push((entry.0.to_owned(), entry.1.as_string().unwrap().to_owned()))
+    for entry in pairs {
+        let value = entry.1.as_string().ok_or_else(|| {
+            DataWatchError::Format(format!("variable {} is not a string: {}", entry.0, entry.1))
+        })?;
+        out.push((entry.0.to_owned(), value.to_owned()))
     }
-    out
+    Ok(out)
 }
 
+/// parse raw json and run the jmespath expression over it
+pub fn search_json(
+    expression: &jmespatch::Expression<'static>,
+    json_response: &str,
+) -> DataWatchResult<jmespatch::Rcvar> {
+    let parsed_json =
+        jmespatch::Variable::from_json(json_response).map_err(DataWatchError::Json)?;
+    let result = expression.search(parsed_json)?;
+    debug!("Parsed result: {:?}", result);
+    Ok(result)
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     #[test]
     fn json_parsing_to_pairs() {
@@ -87,7 +361,7 @@ mod tests {
         let expression =
             jmespatch::compile("{ variable_data: variable_data, variable_name: variable_name }")
                 .unwrap();
-        let parsed = parse_json_pair(&expression, json_raw);
+        let parsed = parse_json_pair(&expression, json_raw).unwrap();
         let mut reader = HashMap::new();
         for entry in parsed.iter() {
             reader.insert(entry.0.to_owned(), entry.1.to_owned());
@@ -100,8 +374,8 @@ mod tests {
     fn json_parsing_single_dataresponse() {
         // OR Single measure in one query (includes multiple measure types)
         // { measure_name: "", measure_data: {measure_desc1: measure_value1, measure_desc2: measure_value2} }
-        // Return should be Hashmap<string, Vec<(String, f64)>
-        // <measure_name, Vec<measure_desc, measure_value)>>
+        // Return should be Hashmap<string, Vec<DataPoint>>
+        // <measure_name, Vec<DataPoint { desc, value, timestamp, labels }>>
         let json_raw = r#" 
         { 
             "measure_name": "name", 
@@ -112,12 +386,12 @@ mod tests {
             }
         } "#;
         let expression = jmespatch::compile("@").unwrap();
-        let datahash = parse_json_data(&expression, &json_raw);
+        let datahash = parse_json_data(&expression, json_raw, &Flatten::default()).unwrap();
         assert_eq!(
             datahash.get(&String::from("name")).unwrap(),
             &vec!(
-                (String::from("desc1"), 1.0 as f64),
-                (String::from("desc2"), 2.0 as f64)
+                DataPoint::new(String::from("desc1"), MeasureValue::Float(1.0), None),
+                DataPoint::new(String::from("desc2"), MeasureValue::Float(2.0), None)
             )
         );
     }
@@ -126,8 +400,8 @@ mod tests {
     fn json_parsing_multiple_dataresponse() {
         // OR Single measure in one query (includes multiple measure types)
         // { measure_name: "", measure_data: {measure_desc1: measure_value1, measure_desc2: measure_value2} }
-        // Return should be Hashmap<string, Vec<(String, f64)>
-        // <measure_name, Vec<measure_desc, measure_value)>>
+        // Return should be Hashmap<string, Vec<DataPoint>>
+        // <measure_name, Vec<DataPoint { desc, value, timestamp, labels }>>
         let json_raw = r#" 
         [
             { 
@@ -149,21 +423,232 @@ mod tests {
         ] "#;
 
         let expression = jmespatch::compile("@").unwrap();
-        let datahash = parse_json_data(&expression, &json_raw);
+        let datahash = parse_json_data(&expression, json_raw, &Flatten::default()).unwrap();
         assert_eq!(
             datahash.get(&String::from("name1")).unwrap(),
             &vec!(
-                (String::from("desc1"), 1.0 as f64),
-                (String::from("desc2"), 2.0 as f64)
+                DataPoint::new(String::from("desc1"), MeasureValue::Float(1.0), None),
+                DataPoint::new(String::from("desc2"), MeasureValue::Float(2.0), None)
             )
         );
         assert_eq!(
             datahash.get(&String::from("name2")).unwrap(),
             &vec!(
-                (String::from("desc1"), 3.0 as f64),
-                (String::from("desc2"), 4.0 as f64)
+                DataPoint::new(String::from("desc1"), MeasureValue::Float(3.0), None),
+                DataPoint::new(String::from("desc2"), MeasureValue::Float(4.0), None)
             )
         );
     }
 
+    #[test]
+    fn json_parsing_missing_measure_name() {
+        let json_raw = r#" { "measure_data": { "desc1": 1.0 } } "#;
+        let expression = jmespatch::compile("@").unwrap();
+        assert!(matches!(
+            parse_json_data(&expression, json_raw, &Flatten::default()),
+            Err(DataWatchError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn json_parsing_labels() {
+        let json_raw = r#"
+        [
+            { "measure_name": "BTC", "measure_data": { "price": 1.5 },
+              "labels": { "exchange": "kraken", "tier": 2, "margin": true } },
+            { "measure_name": "BTC", "measure_data": { "price": 1.6 },
+              "labels": { "exchange": "coinbase", "fees": { "maker": 0.1 } } }
+        ] "#;
+        let expression = jmespatch::compile("@").unwrap();
+        assert!(parse_json_data(&expression, json_raw, &Flatten::default()).is_err());
+
+        let (datahash, dropped) =
+            parse_json_data_lenient(&expression, json_raw, &Flatten::default()).unwrap();
+        let labels: Vec<Vec<(&str, &str)>> = datahash["BTC"]
+            .iter()
+            .map(|point| {
+                point
+                    .labels
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str()))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                vec![("exchange", "kraken"), ("margin", "true"), ("tier", "2")],
+                vec![("exchange", "coinbase")],
+            ]
+        );
+        assert_eq!(
+            dropped[0].to_string(),
+            "BTC.labels.fees is not a number, boolean or string: {\"maker\":0.1}"
+        );
+    }
+
+    #[test]
+    fn json_parsing_typed_values() {
+        let json_raw = r#"
+        {
+            "measure_name": "SPY",
+            "measure_data": {
+                "open": true,
+                "volume": 9007199254740993,
+                "price": 412.5,
+                "status": "halted"
+            }
+        } "#;
+        let expression = jmespatch::compile("@").unwrap();
+        let values: HashMap<String, MeasureValue> =
+            parse_json_data(&expression, json_raw, &Flatten::default())
+                .unwrap()
+                .remove("SPY")
+                .unwrap()
+                .into_iter()
+                .map(|point| (point.desc, point.value))
+                .collect();
+        assert_eq!(values["open"], MeasureValue::Bool(true));
+        assert_eq!(values["volume"], MeasureValue::Integer(9007199254740993));
+        assert_eq!(values["price"], MeasureValue::Float(412.5));
+        assert_eq!(values["status"], MeasureValue::from("halted"));
+    }
+
+    #[test]
+    fn json_parsing_measure_timestamps() {
+        let json_raw = r#"
+        [
+            { "measure_name": "seconds", "measure_data": { "v": 1 }, "timestamp": 1620000000 },
+            { "measure_name": "millis", "measure_data": { "v": 1 }, "timestamp": 1620000000250 },
+            { "measure_name": "rfc3339", "measure_data": { "v": 1 }, "timestamp": "2021-05-03T02:00:00+02:00" },
+            { "measure_name": "missing", "measure_data": { "v": 1 }, "timestamp": null }
+        ] "#;
+        let expression = jmespatch::compile("@").unwrap();
+        let datahash = parse_json_data(&expression, json_raw, &Flatten::default()).unwrap();
+        let timestamp = |name: &str| datahash[name][0].timestamp;
+        assert_eq!(timestamp("seconds"), Some(1620000000));
+        assert_eq!(timestamp("millis"), Some(1620000000));
+        assert_eq!(timestamp("rfc3339"), Some(1620000000));
+        assert_eq!(timestamp("missing"), None);
+
+        let json_raw =
+            r#" { "measure_name": "name", "measure_data": { "v": 1 }, "timestamp": "yesterday" } "#;
+        assert!(matches!(
+            parse_json_data(&expression, json_raw, &Flatten::default()),
+            Err(DataWatchError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn json_parsing_flattens_nested_data() {
+        let json_raw = r#"
+        {
+            "measure_name": "Houston",
+            "measure_data": {
+                "main": { "temp": 81.5 },
+                "wind": { "speed": 3.1, "gust": { "max": 9 } },
+                "bands": [{ "value": 1 }, { "value": 2 }]
+            }
+        } "#;
+        let expression = jmespatch::compile("@").unwrap();
+        let descriptors = |flatten: &Flatten| {
+            let mut data = parse_json_data(&expression, json_raw, flatten).unwrap();
+            let mut descriptors: Vec<_> = data
+                .remove("Houston")
+                .unwrap()
+                .into_iter()
+                .map(|point| point.desc)
+                .collect();
+            descriptors.sort_unstable();
+            descriptors
+        };
+        assert_eq!(
+            descriptors(&Flatten::default()),
+            vec![
+                "bands[0].value",
+                "bands[1].value",
+                "main.temp",
+                "wind.gust.max",
+                "wind.speed"
+            ]
+        );
+        assert_eq!(
+            descriptors(&Flatten {
+                separator: String::from("_"),
+                max_depth: 5,
+            })[2],
+            "main_temp"
+        );
+
+        let shallow = Flatten {
+            separator: String::from("."),
+            max_depth: 2,
+        };
+        assert!(parse_json_data(&expression, json_raw, &shallow).is_err());
+        let (_, dropped) = parse_json_data_lenient(&expression, json_raw, &shallow).unwrap();
+        assert_eq!(dropped.len(), 3);
+        assert_eq!(
+            dropped[0].to_string(),
+            "Houston.bands[0] is nested more than 2 levels deep"
+        );
+    }
+
+    #[test]
+    fn json_parsing_value_not_a_measure() {
+        let json_raw = r#" { "measure_name": "name", "measure_data": { "desc1": null } } "#;
+        let expression = jmespatch::compile("@").unwrap();
+        assert!(matches!(
+            parse_json_data(&expression, json_raw, &Flatten::default()),
+            Err(DataWatchError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn json_parsing_lenient_reports_dropped_fields() {
+        let json_raw = r#"
+        {
+            "measure_name": "BTC-USD",
+            "measure_data": {
+                "price": "43000.12",
+                "trades": "1200",
+                "side": "buy",
+                "bid": null,
+                "book": [[[[[1]]]]]
+            },
+            "timestamp": "soon"
+        } "#;
+        let expression = jmespatch::compile("@").unwrap();
+        assert!(parse_json_data(&expression, json_raw, &Flatten::default()).is_err());
+
+        let (mut data, dropped) =
+            parse_json_data_lenient(&expression, json_raw, &Flatten::default()).unwrap();
+        let mut points = data.remove("BTC-USD").unwrap();
+        points.sort_by(|a, b| a.desc.cmp(&b.desc));
+        assert_eq!(
+            points,
+            vec![
+                DataPoint::new(String::from("price"), MeasureValue::Float(43000.12), None),
+                DataPoint::new(String::from("side"), MeasureValue::from("buy"), None),
+                DataPoint::new(String::from("trades"), MeasureValue::Integer(1200), None),
+            ]
+        );
+        let mut fields: Vec<_> = dropped.iter().map(|field| field.field.as_str()).collect();
+        fields.sort_unstable();
+        assert_eq!(fields, vec!["bid", "book[0][0][0][0]", "timestamp"]);
+        assert!(dropped[0].to_string().starts_with("BTC-USD."));
+    }
+
+    #[test]
+    fn json_parsing_invalid_response() {
+        let json_raw = "<html>Service Unavailable</html>";
+        let expression = jmespatch::compile("@").unwrap();
+        assert!(matches!(
+            parse_json_data(&expression, json_raw, &Flatten::default()),
+            Err(DataWatchError::Json(_))
+        ));
+        assert!(matches!(
+            parse_json_pair(&expression, json_raw),
+            Err(DataWatchError::Json(_))
+        ));
+    }
 }
@@ -1,25 +1,57 @@
-use std::collections::HashMap;
+use crate::actors::messages::MeasureValue;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::sync::RwLock;
 
 pub mod actors;
+pub mod config;
+pub mod error;
 
 // Utility
-// Collection of functions to store variables in shared storage, parse json to match data model, and
-// read stored variables to update strings
+// Collection of functions to store variables in shared storage, parse json to match data model,
+// read stored variables to update strings, and work out when a schedule fires or skips a date
+pub mod calendar;
 pub mod jsonutility;
+pub mod schedule;
 pub mod varstore;
 
+#[cfg(test)]
+mod testserver;
+
 // global variables that can be used to implement in Producer configuration
 pub type SharedVar = Arc<RwLock<HashMap<String, String>>>;
-pub type DataSource = HashMap<String, Vec<(String, f64)>>;
-pub type VarPairs = Vec<(String, String)>; 
+// data points keyed by measure_name
+pub type DataSource = HashMap<String, Vec<DataPoint>>;
+pub type VarPairs = Vec<(String, String)>;
+// extra dimensions of a data point such as exchange or currency, in name order
+pub type Labels = BTreeMap<String, String>;
+
+/// Value of a measure read from a response, becomes a `DataResponse` when published
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataPoint {
+    /// measure_desc
+    pub desc: String,
+    pub value: MeasureValue,
+    /// time from the response, None to use the publish time
+    pub timestamp: Option<u64>,
+    pub labels: Labels,
+}
+
+impl DataPoint {
+    /// data point without labels
+    pub fn new(desc: String, value: MeasureValue, timestamp: Option<u64>) -> Self {
+        DataPoint {
+            desc,
+            value,
+            timestamp,
+            labels: Labels::new(),
+        }
+    }
+}
 
 // POSSIBLE:
 // create generic producer
 // move web items out of actor to run
-// what about interval vs cron schedule (new enum that includes either)
-// what about non-fire days (like holidays?)
 
 // TODO: can we stream cron dates instead of just iterating?
 // TODO: selection trait on data to be used in different consumers
@@ -27,15 +59,13 @@ pub type VarPairs = Vec<(String, String)>;
 // action to define appending or latest / format csv, json, others?)
 // TODO: add memory cache actor for measures that is live over a finite time, able to register which variables
 // TODO: Selective printing or csv storage
-// TODO: Configuration can be saved and loaded - example combining Weather and Quote
+// TODO: Configuration can be saved (loading is in config.rs)
 // TODO: Alert consumer that matches criteria
-// TODO: Need error checking
 // TODO: convert measure (function) or combination of measures to calculated new measure - New Producer
 // TODO: to database consumer
 // TODO: program initiation structure to keep track of actors to minimize users of library having to initialize everything
 // TODO: an elegant way to exit the program
 // TODO: a way to interact with the program either through CLI or server or both
-// TODO: better Error handling - think about all the things that could go wrong and create error type or anyhow
 // TODO: better way to deal with dates  (maybe a utility folder with utility.rs -> parsing, dates)
 
 // FUTURE: Add an ability to push items to data-watch from outside
@@ -0,0 +1,214 @@
+use data_watch::actors::consumer::{CsvConsumer, StdoutConsumer};
+use data_watch::actors::messages::Stop;
+use data_watch::actors::producer::{
+    configure_client, configure_fixtures, FixtureMode, OAuth2Provider, ProducerAction, RateLimiter,
+    RequestAuth, WebProducer,
+};
+use data_watch::actors::{dependency_order, Scheduler};
+use data_watch::config::{Config, ConsumerConfig};
+use log::info;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use xactor::Actor;
+
+// Runs a configuration file, see config.rs for the format
+//
+// data-watch run <config>       start consumers and schedules, runs until ctrl-c or SIGTERM
+// data-watch validate <config>  check configuration and exit
+// data-watch once <config>      run every schedule one time and print the data
+//
+// run and once take --record <dir> to save every response as a fixture, or
+// --replay <dir> to answer requests from saved fixtures without the network.
+// Known credentials and those of the schedule auth are saved as REDACTED, other values in
+// fixtures are saved as sent. Replay needs no credentials, unset variables hold REDACTED
+
+const USAGE: &str =
+    "usage: data-watch <run|validate|once> <config> [--record <dir>|--replay <dir>]";
+
+#[async_std::main]
+async fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (command, path, fixtures) = match args.as_slice() {
+        [command, path] => (command.as_str(), path, None),
+        [command, path, option, dir] if command != "validate" => {
+            let dir = PathBuf::from(dir);
+            match option.as_str() {
+                "--record" => (command.as_str(), path, Some(FixtureMode::Record(dir))),
+                "--replay" => (command.as_str(), path, Some(FixtureMode::Replay(dir))),
+                _ => exit_with_usage(),
+            }
+        }
+        _ => exit_with_usage(),
+    };
+    let loaded = match fixtures {
+        Some(FixtureMode::Replay(_)) => Config::from_file_for_replay(path),
+        _ => Config::from_file(path),
+    };
+    let config = match loaded {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    if command == "run" || command == "once" {
+        let configured = configure_client(&config.client)
+            .and_then(|_| fixtures.map_or(Ok(()), configure_fixtures));
+        if let Err(e) = configured {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+
+    let result = match command {
+        "run" => run(config).await,
+        "validate" => {
+            println!(
+                "{}: ok - {} schedules, {} consumers, {} calendars",
+                path,
+                config.schedules.len(),
+                config.consumers.len(),
+                config.calendars.len()
+            );
+            Ok(())
+        }
+        "once" => once(config).await,
+        _ => exit_with_usage(),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// start scheduler, consumers and schedules then wait for a signal to stop
+async fn run(config: Config) -> xactor::Result<()> {
+    let scheduler = Scheduler::default().start().await?;
+    let scheduler_addr = scheduler.clone();
+
+    // send scheduler clone to watch for shutdown
+    let scheduler_task = xactor::spawn(async {
+        scheduler.wait_for_stop().await;
+    });
+
+    // keep consumer addresses until the scheduler stops
+    let mut stdout_consumers = Vec::new();
+    let mut csv_consumers = Vec::new();
+    for consumer in config.consumers.iter() {
+        match consumer {
+            ConsumerConfig::Stdout => stdout_consumers.push(StdoutConsumer.start().await?),
+            ConsumerConfig::Csv { path } => {
+                csv_consumers.push(CsvConsumer::open(path).await?.start().await?)
+            }
+        }
+    }
+
+    for calendar in config.calendars {
+        scheduler_addr.send(calendar)?;
+    }
+    for provider in config.auth_providers {
+        scheduler_addr.send(provider)?;
+    }
+    for rate_limit in config.rate_limits {
+        scheduler_addr.send(rate_limit)?;
+    }
+    for schedule in config.schedules {
+        scheduler_addr.send(schedule)?;
+    }
+
+    let stop_addr = scheduler_addr.clone();
+    ctrlc::set_handler(move || {
+        info!("Signal received, stopping scheduler");
+        stop_addr.send(Stop).ok();
+    })?;
+
+    scheduler_task.await;
+    info!(
+        "Stopped {} consumers",
+        stdout_consumers.len() + csv_consumers.len()
+    );
+    Ok(())
+}
+
+/// run every schedule one time after the schedules it depends on, variable schedules first
+/// otherwise, and print the data
+async fn once(config: Config) -> xactor::Result<()> {
+    let (variables, data): (Vec<_>, Vec<_>) = config
+        .schedules
+        .into_iter()
+        .partition(|schedule| matches!(schedule.response_action, ProducerAction::STOREVARIABLE));
+    let schedules: Vec<_> = variables.into_iter().chain(data).collect();
+    let order = dependency_order(&schedules)?;
+    let mut schedules: Vec<_> = schedules.into_iter().map(Some).collect();
+    let providers: HashMap<String, Arc<OAuth2Provider>> = config
+        .auth_providers
+        .into_iter()
+        .map(|provider| {
+            let name = provider.0.name.clone();
+            (name, Arc::new(OAuth2Provider::new(provider.0)))
+        })
+        .collect();
+    let limiters: HashMap<String, Arc<RateLimiter>> = config
+        .rate_limits
+        .into_iter()
+        .map(|rate_limit| {
+            let name = rate_limit.0.name.clone();
+            (name, Arc::new(RateLimiter::new(rate_limit.0)))
+        })
+        .collect();
+    let total = schedules.len();
+    let mut failed = 0;
+    for schedule in order
+        .into_iter()
+        .filter_map(|position| schedules[position].take())
+    {
+        let source_name = schedule.source_name.clone();
+        let provider = match &schedule.auth {
+            Some(RequestAuth::OAuth2(name)) => providers.get(name).cloned(),
+            _ => None,
+        };
+        let limiter =
+            RateLimiter::select(&limiters, schedule.rate_limit.as_deref(), &schedule.api_url);
+        let result = match (WebProducer::new(schedule), provider, limiter) {
+            (Ok(mut producer), provider, Ok(limiter)) => {
+                if let Some(provider) = provider {
+                    producer = producer.with_auth_provider(provider);
+                }
+                if let Some(limiter) = limiter {
+                    producer = producer.with_rate_limiter(limiter);
+                }
+                producer.run_once().await
+            }
+            (Err(e), _, _) | (_, _, Err(e)) => Err(e),
+        };
+        match result {
+            Ok(data_responses) => {
+                for data in data_responses {
+                    println!("{:?}", data);
+                }
+            }
+            Err(e) => {
+                eprintln!("{}: {}", source_name, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(xactor::Error::msg(format!(
+            "{} of {} schedules failed",
+            failed, total
+        )));
+    }
+    Ok(())
+}
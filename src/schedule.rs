@@ -0,0 +1,331 @@
+use crate::calendar::Calendar;
+use crate::error::{DataWatchError, DataWatchResult};
+use chrono::{DateTime, LocalResult, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Defines the timing of a producer request
+///
+/// Cron uses the seconds format: "sec min hour dayofmonth month dayofweek"
+/// and is evaluated in the schedule timezone, UTC unless set
+///
+/// Interval fires every `every`, at least 1 ms, anchored at the time the producer starts.
+/// The first run happens after `offset`, or right away if there is no offset
+#[derive(Debug, Clone)]
+pub enum ScheduleType {
+    Cron(String),
+    Interval {
+        every: Duration,
+        offset: Option<Duration>,
+    },
+}
+
+/// Parsed `ScheduleType` used by a producer to find its next fire time
+/// Fire times falling on a date excluded by the calendar are skipped
+///
+/// Cron fire times are local times in the timezone. Across DST transitions:
+/// - a local time skipped when clocks go forward fires at the same offset from the
+///   transition (2:30 becomes 3:30)
+/// - a local time repeated when clocks go back fires once, on its first occurrence
+///
+/// Returned fire times are always UTC
+pub struct FireSchedule {
+    timing: Timing,
+    calendar: Option<Arc<Calendar>>,
+    timezone: Tz,
+}
+
+enum Timing {
+    Cron(Box<cron::Schedule>),
+    Interval {
+        anchor: DateTime<Utc>,
+        every: chrono::Duration,
+    },
+}
+
+impl FireSchedule {
+    /// parse schedule, an interval is anchored at the current time
+    pub fn new(schedule: &ScheduleType) -> DataWatchResult<Self> {
+        FireSchedule::anchored_at(schedule, Utc::now())
+    }
+
+    fn anchored_at(schedule: &ScheduleType, start: DateTime<Utc>) -> DataWatchResult<Self> {
+        let timing = match schedule {
+            ScheduleType::Cron(expression) => Timing::Cron(Box::new(
+                cron::Schedule::from_str(expression)
+                    .map_err(|e| DataWatchError::Schedule(format!("{}: {}", expression, e)))?,
+            )),
+            ScheduleType::Interval { every, offset } => {
+                let every = chrono::Duration::from_std(*every)
+                    .ok()
+                    .filter(|every| *every >= chrono::Duration::milliseconds(1))
+                    .ok_or_else(|| {
+                        DataWatchError::Schedule(format!("invalid interval {:?}", every))
+                    })?;
+                let offset =
+                    chrono::Duration::from_std(offset.unwrap_or_default()).map_err(|_| {
+                        DataWatchError::Schedule(format!("invalid offset {:?}", offset))
+                    })?;
+                Timing::Interval {
+                    anchor: start + offset,
+                    every,
+                }
+            }
+        };
+        Ok(FireSchedule {
+            timing,
+            calendar: None,
+            timezone: Tz::UTC,
+        })
+    }
+
+    /// evaluate cron and calendar dates in IANA timezone like "America/New_York"
+    pub fn with_timezone(mut self, timezone: &str) -> DataWatchResult<Self> {
+        self.timezone = timezone.parse().map_err(|e| {
+            DataWatchError::Schedule(format!("unknown timezone {}: {}", timezone, e))
+        })?;
+        Ok(self)
+    }
+
+    /// skip fire times on dates excluded by calendar
+    pub fn with_calendar(mut self, calendar: Arc<Calendar>) -> Self {
+        self.calendar = Some(calendar);
+        self
+    }
+
+    /// first fire time for a producer starting at `now`, an interval fires at its anchor
+    /// even when `now` is already past it
+    pub fn first_after(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match &self.timing {
+            Timing::Interval { anchor, .. } if now >= *anchor => {
+                self.next_after(*anchor - chrono::Duration::nanoseconds(1))
+            }
+            _ => self.next_after(now),
+        }
+    }
+
+    /// next fire time strictly after `after`, None if schedule has no more fire times
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut after = after;
+        loop {
+            let next = self.next_timing_after(after)?;
+            let date = next.with_timezone(&self.timezone).date().naive_local();
+            match &self.calendar {
+                Some(calendar) if calendar.is_excluded(date) => {
+                    // continue search from the start of the next date that is not excluded
+                    let open = calendar.next_open_date(date).and_hms(0, 0, 0);
+                    after = self.local_to_utc(open, None)? - chrono::Duration::nanoseconds(1);
+                }
+                _ => return Some(next),
+            }
+        }
+    }
+
+    fn next_timing_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match &self.timing {
+            Timing::Cron(schedule) => {
+                // run cron over local wall clock time, Utc is only a stand in for naive time
+                let local_after = after.with_timezone(&self.timezone).naive_local();
+                schedule
+                    .after(&Utc.from_utc_datetime(&local_after))
+                    .find_map(|local| self.local_to_utc(local.naive_utc(), Some(after)))
+            }
+            Timing::Interval { anchor, every } => {
+                if after < *anchor {
+                    return Some(*anchor);
+                }
+                let every = every.num_milliseconds();
+                let periods = (after - *anchor).num_milliseconds() / every + 1;
+                Some(*anchor + chrono::Duration::milliseconds(periods * every))
+            }
+        }
+    }
+
+    /// convert local time in the timezone to utc, strictly after `after` when given
+    fn local_to_utc(
+        &self,
+        local: NaiveDateTime,
+        after: Option<DateTime<Utc>>,
+    ) -> Option<DateTime<Utc>> {
+        let is_after = |time: &DateTime<Utc>| after.is_none_or(|after| *time > after);
+        match self.timezone.from_local_datetime(&local) {
+            LocalResult::Single(time) => Some(time.with_timezone(&Utc)).filter(is_after),
+            // repeated local time, take the first occurrence not already passed
+            LocalResult::Ambiguous(earliest, latest) => [earliest, latest]
+                .iter()
+                .map(|time| time.with_timezone(&Utc))
+                .find(is_after),
+            // skipped local time, use the offset from before the transition
+            LocalResult::None => {
+                let before = self
+                    .timezone
+                    .offset_from_utc_datetime(&(local - chrono::Duration::days(1)))
+                    .fix();
+                Some(DateTime::<Utc>::from_utc(local - before, Utc)).filter(is_after)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn interval_anchored_at_start_with_offset() {
+        let start = Utc.ymd(2021, 3, 1).and_hms(12, 0, 0);
+        let schedule = FireSchedule::anchored_at(
+            &ScheduleType::Interval {
+                every: Duration::from_secs(90),
+                offset: Some(Duration::from_secs(10)),
+            },
+            start,
+        )
+        .unwrap();
+        assert_eq!(
+            schedule.next_after(start),
+            Some(Utc.ymd(2021, 3, 1).and_hms(12, 0, 10))
+        );
+        assert_eq!(
+            schedule.next_after(Utc.ymd(2021, 3, 1).and_hms(12, 0, 10)),
+            Some(Utc.ymd(2021, 3, 1).and_hms(12, 1, 40))
+        );
+        assert_eq!(
+            schedule.next_after(Utc.ymd(2021, 3, 1).and_hms(12, 5, 0)),
+            Some(Utc.ymd(2021, 3, 1).and_hms(12, 6, 10))
+        );
+    }
+
+    #[test]
+    fn interval_without_offset_fires_at_start() {
+        let start = Utc.ymd(2021, 3, 1).and_hms(12, 0, 0);
+        let schedule = FireSchedule::anchored_at(
+            &ScheduleType::Interval {
+                every: Duration::from_secs(90),
+                offset: None,
+            },
+            start,
+        )
+        .unwrap();
+        let now = start + chrono::Duration::milliseconds(5);
+        assert_eq!(schedule.first_after(now), Some(start));
+        assert_eq!(
+            schedule.next_after(now),
+            Some(Utc.ymd(2021, 3, 1).and_hms(12, 1, 30))
+        );
+    }
+
+    #[test]
+    fn cron_next_fire_time() {
+        let schedule =
+            FireSchedule::new(&ScheduleType::Cron(String::from("0 */15 * * * *"))).unwrap();
+        assert_eq!(
+            schedule.next_after(Utc.ymd(2021, 3, 1).and_hms(12, 7, 0)),
+            Some(Utc.ymd(2021, 3, 1).and_hms(12, 15, 0))
+        );
+    }
+
+    #[test]
+    fn skip_calendar_dates() {
+        // weekdays at 9:30, friday the 2nd of april 2021 is a holiday
+        let calendar = Calendar::parse("NYSE", "2021-04-02").unwrap();
+        let schedule = FireSchedule::new(&ScheduleType::Cron(String::from("0 30 9 * * Mon-Fri")))
+            .unwrap()
+            .with_calendar(Arc::new(calendar));
+        assert_eq!(
+            schedule.next_after(Utc.ymd(2021, 4, 1).and_hms(9, 0, 0)),
+            Some(Utc.ymd(2021, 4, 1).and_hms(9, 30, 0))
+        );
+        assert_eq!(
+            schedule.next_after(Utc.ymd(2021, 4, 1).and_hms(10, 0, 0)),
+            Some(Utc.ymd(2021, 4, 5).and_hms(9, 30, 0))
+        );
+    }
+
+    #[test]
+    fn cron_in_timezone_across_dst() {
+        let schedule = FireSchedule::new(&ScheduleType::Cron(String::from("0 30 9 * * *")))
+            .unwrap()
+            .with_timezone("America/New_York")
+            .unwrap();
+        // EST is UTC-5, EDT from 14th of march 2021 is UTC-4
+        assert_eq!(
+            schedule.next_after(Utc.ymd(2021, 3, 13).and_hms(12, 0, 0)),
+            Some(Utc.ymd(2021, 3, 13).and_hms(14, 30, 0))
+        );
+        assert_eq!(
+            schedule.next_after(Utc.ymd(2021, 3, 13).and_hms(15, 0, 0)),
+            Some(Utc.ymd(2021, 3, 14).and_hms(13, 30, 0))
+        );
+    }
+
+    #[test]
+    fn cron_in_skipped_and_repeated_local_time() {
+        let skipped = FireSchedule::new(&ScheduleType::Cron(String::from("0 30 2 * * *")))
+            .unwrap()
+            .with_timezone("America/New_York")
+            .unwrap();
+        // 2:30 does not exist on 14th of march 2021, fires at 3:30 EDT
+        assert_eq!(
+            skipped.next_after(Utc.ymd(2021, 3, 14).and_hms(0, 0, 0)),
+            Some(Utc.ymd(2021, 3, 14).and_hms(7, 30, 0))
+        );
+
+        let repeated = FireSchedule::new(&ScheduleType::Cron(String::from("0 30 1 * * *")))
+            .unwrap()
+            .with_timezone("America/New_York")
+            .unwrap();
+        // 1:30 happens twice on 7th of november 2021, fires on 1:30 EDT only
+        let first = repeated.next_after(Utc.ymd(2021, 11, 7).and_hms(0, 0, 0));
+        assert_eq!(first, Some(Utc.ymd(2021, 11, 7).and_hms(5, 30, 0)));
+        assert_eq!(
+            repeated.next_after(first.unwrap()),
+            Some(Utc.ymd(2021, 11, 8).and_hms(6, 30, 0))
+        );
+    }
+
+    #[test]
+    fn calendar_dates_in_timezone() {
+        // 1:00 UTC on the 5th of april is still the 4th in New York
+        let calendar = Calendar::parse("HOLIDAY", "2021-04-04").unwrap();
+        let schedule = FireSchedule::new(&ScheduleType::Cron(String::from("0 0 * * * *")))
+            .unwrap()
+            .with_timezone("America/New_York")
+            .unwrap()
+            .with_calendar(Arc::new(calendar));
+        assert_eq!(
+            schedule.next_after(Utc.ymd(2021, 4, 5).and_hms(0, 30, 0)),
+            Some(Utc.ymd(2021, 4, 5).and_hms(4, 0, 0))
+        );
+    }
+
+    #[test]
+    fn invalid_schedules() {
+        assert!(FireSchedule::new(&ScheduleType::Cron(String::from("not a cron"))).is_err());
+        assert!(FireSchedule::new(&ScheduleType::Interval {
+            every: Duration::from_secs(0),
+            offset: None
+        })
+        .is_err());
+        assert!(
+            FireSchedule::new(&ScheduleType::Cron(String::from("0 0 * * * *")))
+                .unwrap()
+                .with_timezone("Mars/Olympus_Mons")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn interval_below_one_millisecond_rejected() {
+        let interval = |every| ScheduleType::Interval {
+            every,
+            offset: None,
+        };
+        assert!(FireSchedule::new(&interval(Duration::from_micros(500))).is_err());
+        assert!(FireSchedule::new(&interval(Duration::from_micros(999))).is_err());
+        assert!(FireSchedule::new(&interval(Duration::from_millis(1))).is_ok());
+    }
+}
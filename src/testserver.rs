@@ -0,0 +1,136 @@
+//! Minimal http server for tests, answers each connection with the next canned response
+use async_std::io::BufReader;
+use async_std::net::{TcpListener, TcpStream};
+use async_std::prelude::*;
+use async_std::task;
+use std::sync::{Arc, Mutex};
+
+/// canned response, `Connection: close` is always added
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl TestResponse {
+    pub fn ok(body: &str) -> Self {
+        TestResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: body.to_owned(),
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        TestResponse {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+}
+
+/// request as received, `head` holds the request line and headers
+#[derive(Debug, Clone)]
+pub struct TestRequest {
+    pub head: String,
+    pub body: String,
+}
+
+impl TestRequest {
+    /// value of the first header named `name`, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    }
+}
+
+pub struct TestServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<TestRequest>>>,
+}
+
+impl TestServer {
+    /// serve `responses` in order, the last one repeats
+    pub async fn start(responses: Vec<TestResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        task::spawn(async move {
+            let mut incoming = listener.incoming();
+            let mut served = 0;
+            while let Some(Ok(stream)) = incoming.next().await {
+                let response = &responses[served.min(responses.len() - 1)];
+                served += 1;
+                if let Some(request) = read_request(&stream).await {
+                    received.lock().unwrap().push(request);
+                    write_response(stream, response).await;
+                }
+            }
+        });
+        TestServer { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<TestRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn read_request(mut stream: &TcpStream) -> Option<TestRequest> {
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.ok()? == 0 {
+            return None;
+        }
+        if line == "\r\n" {
+            break;
+        }
+        head.push_str(&line);
+    }
+    let mut request = TestRequest {
+        head,
+        body: String::new(),
+    };
+    if request.header("expect") == Some("100-continue") {
+        stream
+            .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+            .await
+            .ok()?;
+    }
+    let length = request
+        .header("content-length")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await.ok()?;
+    request.body = String::from_utf8_lossy(&body).into_owned();
+    Some(request)
+}
+
+async fn write_response(mut stream: TcpStream, response: &TestResponse) {
+    let headers: String = response
+        .headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect();
+    let text = format!(
+        "HTTP/1.1 {} Test\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        headers,
+        response.body.len(),
+        response.body
+    );
+    stream.write_all(text.as_bytes()).await.ok();
+}
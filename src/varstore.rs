@@ -1,7 +1,7 @@
-use crate::VarPairs;
-use percent_encoding::{NON_ALPHANUMERIC, percent_encode};
 use crate::SharedVar;
+use crate::VarPairs;
 use log::debug;
+use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
 
 /// Update string to replace [[VARIABLE]] with a variable stored in shared variables
 /// Created as recursive funciton to add multiple variables to one string
@@ -27,7 +27,7 @@ pub fn swap_variable(storage_var: &SharedVar, text: &str, encode: bool) -> Strin
                 } else {
                     newtext = text.replace(&text[start..end + 2], "");
                 }
-                newtext = swap_variable(&storage_var, newtext.as_ref(), encode);
+                newtext = swap_variable(storage_var, newtext.as_ref(), encode);
             }
         }
     }
@@ -39,18 +39,14 @@ pub fn swap_variable(storage_var: &SharedVar, text: &str, encode: bool) -> Strin
 ///
 /// Parsed format:
 /// { "name1": "data1", "name2": "data2" }
-pub fn store_variable(
-    storage_var: &SharedVar,
-    pairs: &VarPairs,
-) {
+pub fn store_variable(storage_var: &SharedVar, pairs: &VarPairs) {
     let mut storage = storage_var.write().unwrap();
     for entry in pairs.iter() {
         storage.insert(entry.0.to_owned(), entry.1.to_owned());
-        debug!("Shared Variables added {:?}", entry);
+        debug!("Shared Variable added: {}", entry.0);
     }
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -91,14 +87,15 @@ mod tests {
     #[test]
     fn pairs_into_shared_variables() {
         let storage_var = Arc::new(RwLock::new(HashMap::new()));
-        let pairs = vec!((String::from("variable_name"), String::from("name")), (String::from("variable_data"), String::from("data")));
+        let pairs = vec![
+            (String::from("variable_name"), String::from("name")),
+            (String::from("variable_data"), String::from("data")),
+        ];
         store_variable(&storage_var, &pairs);
         {
             let reader = storage_var.read().unwrap();
             assert_eq!(reader.get("variable_name").unwrap(), "name");
             assert_eq!(reader.get("variable_data").unwrap(), "data");
         }
-
     }
-
-}
\ No newline at end of file
+}